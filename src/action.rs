@@ -1,93 +1,233 @@
 // Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
 // Licensed under the 2-clause BSD license, see LICENSE for details.
 
-use std::ffi::OsString;
-use std::fs;
-use std::ops::Add;
-use std::process::Command;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
 use std::rc::Rc;
 
 use getopts::Options;
 use time;
+use time::Tm;
 
+use attr::Attr;
+use calendar;
+use color::{self, Style};
+use depends;
 use error::{TodoError, TodoErrorKind, TodoResult};
+use item_file;
+use journal::Op;
+use links;
 use opt::Opt;
-use todo_item::TodoItem;
+use priority::Priority;
+use status::Status;
+use store::Store;
+use template;
+use todo_item::{self, TodoItem};
 use todo_items;
 use util;
 
 #[derive(Clone,Debug,Eq,Ord,PartialEq,PartialOrd)]
 pub enum Action {
+    Add,
     Agenda,
+    Blocked,
+    BulkEdit,
+    Capture,
+    CaptureMail,
+    Check,
+    CheckStep,
     Delete,
+    Done,
     Dump,
     Edit,
+    Graph,
     Help,
+    History,
+    Log,
+    Month,
+    New,
+    Postpone,
+    Ready,
+    Redo,
+    Rollover,
+    Search,
     Show,
+    Tag,
     Today,
     TodayOnly,
+    Tui,
+    Undo,
     Version,
+    Week,
 }
 
+const INDENT: &'static str = "        ";
+// indent of a subtask under its parent in listings
+const SUBTASK_INDENT: &'static str = "  ";
+// more selected items than this need to be confirmed
+const CONFIRM_LIMIT: usize = 3;
+// longest file name made for a new item, without the number
+const MAX_NAME_LEN: usize = 40;
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 const LICENSE_STR: &'static str =
     "Copyright 2016 Tuomo Hartikainen <tth@harski.org>.\n\
      Licensed under the 2-clause BSD license, see LICENSE for details.";
 
 
-pub fn agenda(opt: &Opt, items: &Vec<Rc<TodoItem>>) {
-    // TODO: Error handling, get rid of unwrap()
-    let today: time::Tm = time::now() ;
-    let dur = time::Duration::days(opt.agenda_days);
-    let up_limit = today.add(dur);
-    let limit_str = util::date_to_str(&up_limit).unwrap();
+/// Check or uncheck step `opt.step` of the checklist of the selected item.
+pub fn check_step(opt: &Opt, store: &dyn Store, items: &Vec<Rc<TodoItem>>)
+                  -> TodoResult<()> {
+    let item = match try!(find_items(opt, items)).into_iter().next() {
+        Some(item)  => item,
+        None        => return Ok(()),
+    };
+    let mut op = Op::new("check-step", &format!("Toggle step {} of '{}'",
+                                                opt.step, item.heading));
+    try!(op.touch(store, &item.filename));
+    let result = item_file::toggle_step(store, &item, opt.step);
+    try!(op.finish(opt, store));
+    let step = try!(result);
+    let mark = if step.done { " " } else { "x" };
+    println!("{}[{:3}]: {}: [{}] {}", INDENT, item.id, item.heading, mark,
+             step.text);
+    Ok(())
+}
 
-    let undone = todo_items::get_undone_items(&items);
-    let before = todo_items::get_items_before(&undone, &limit_str);
-    let agenda = todo_items::get_items_after(&before,
-                                             &util::date_to_str(&today).unwrap());
+
+/// Add an item with `heading`, dated today. With `--parent` it is a subtask
+/// of the item given.
+pub fn add_item(opt: &Opt, store: &dyn Store, items: &Vec<Rc<TodoItem>>,
+                heading: &str) -> TodoResult<()> {
+    let heading = heading.trim();
+    if heading.len() == 0 || heading.contains('\n') {
+        let err_msg = format!("Invalid heading '{}'", heading);
+        return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+    }
+    let mut contents = format!("{}\ndate: {}\n", heading,
+                               try!(opt.clock.today_str()));
+    if let Some(ref selector) = opt.parent {
+        let id = try!(todo_items::select_item(items, selector));
+        let parent = match todo_items::get_item_by_id(items, id) {
+            Some(parent)    => parent,
+            None            => {
+                let err_msg = format!("Item {} not found", id);
+                return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+            },
+        };
+        contents.push_str(&format!("{}: {}\n", todo_items::PARENT_ATTR,
+                                   parent.stable_id()));
+    }
+
+    let mut op = Op::new("add", &format!("Add '{}'", heading));
+    let key = try!(save_new_item(store, &mut op, heading, &contents));
+    try!(op.finish(opt, store));
+    println!("Added {}", key);
+    Ok(())
+}
+
+
+/// Get the inclusive date range for the agenda and a description of it.
+pub fn agenda_range(opt: &Opt) -> TodoResult<(Tm, Tm, String)> {
+    let today = opt.clock.today();
+
+    if let Some(days) = opt.agenda_past {
+        if opt.agenda_from.is_some() || opt.agenda_to.is_some() {
+            return Err(TodoError::new(TodoErrorKind::Other,
+                                      "'--past' can not be used with \
+                                      '--from' or '--to'".to_string()));
+        }
+        let from = util::add_days(&today, -days);
+        let to = util::add_days(&today, -1);
+        return Ok((from, to, format!("the past {} days, ", days)));
+    }
+
+    let parse = |arg: &str| {
+        util::parse_date_arg(arg, &today)
+            .map_err(|e| TodoError::new(TodoErrorKind::Other, e))
+    };
+
+    let from = match opt.agenda_from {
+        Some(ref arg)   => try!(parse(arg)),
+        None            => today,
+    };
+
+    match opt.agenda_to {
+        Some(ref arg)   => {
+            let to = try!(parse(arg));
+            if to.to_timespec() < from.to_timespec() {
+                return Err(TodoError::new(TodoErrorKind::Other,
+                                          "Agenda end date is before its \
+                                          start date".to_string()));
+            }
+            Ok((from, to, "".to_string()))
+        },
+        None            => {
+            let to = util::add_days(&from, opt.agenda_days - 1);
+            let desc = if opt.agenda_from.is_some() {
+                "".to_string()
+            } else {
+                format!("the next {} days, ", opt.agenda_days)
+            };
+            Ok((from, to, desc))
+        },
+    }
+}
+
+
+/// Get the items in the agenda range, and a description of the range.
+pub fn agenda_items(opt: &Opt, items: &Vec<Rc<TodoItem>>)
+                    -> TodoResult<(Vec<Rc<TodoItem>>, String)> {
+    let (from, to, desc) = try!(agenda_range(opt));
+    let today_str = try!(opt.clock.today_str());
+    let from_str = try!(util::date_to_str(&from));
+    let to_str = try!(util::date_to_str(&to));
+
+    // past days show what happened, so done items are included for them
+    let in_range = todo_items::get_items_between(&items, &from_str, &to_str);
+    let agenda: Vec<Rc<TodoItem>> = in_range.into_iter().filter(|item| {
+        item.status != Some(Status::Done) ||
+            item.get_date_str().unwrap() < today_str
+    }).collect();
+
+    Ok((agenda, format!("{}{} to {}", desc, from_str, to_str)))
+}
+
+
+pub fn agenda(opt: &Opt, items: &Vec<Rc<TodoItem>>) -> TodoResult<()> {
+    let (agenda, range_str) = try!(agenda_items(opt, items));
 
     // print agenda
     if agenda.len() > 0 {
-        println!("Agenda for the next {} days:", opt.agenda_days);
-
-        let mut date_str: String = agenda.first().unwrap().get_date_str().unwrap();
-        println!("{}:", date_str);
-
-        // loop for different days
-        for item in agenda {
-            // update date if necessary
-            let date_tmp = item.get_date_str().unwrap();
-            if !date_str.eq(&date_tmp) {
-                date_str = date_tmp;
-                println!("\n{}:", date_str);
-            }
-            print_item(&item);
-        }
+        println!("Agenda for {}:", range_str);
+        print_by_date(opt, &agenda, items);
     } else {
-        println!("Agenda is empty for the next {} days.", opt.agenda_days);
+        println!("Agenda is empty for {}.", range_str);
     }
+    Ok(())
 }
 
 
-pub fn delete_item(mut items: &mut Vec<Rc<TodoItem>>, i: i32) {
-    if i != 0 {
-        /* TODO: get_item to remove_item */
-        match todo_items::get_item_by_id(&items, i) {
-            Some(item) => {
-                match fs::remove_file(&item.filename) {
-                    Ok(_)   => { todo_items::remove_item_by_id(&mut items, i); },
-                    Err(e)  =>
-                        print_err!("Error: cannot delete item {}: {}",
-                                   item.id, e),
-                };
-            },
-            None    =>
-                print_err!("Error: cannot delete item {}: item not found", i),
-        };
-    } else {
-        print_err!("Item ID not set");
+pub fn delete_items(opt: &Opt, store: &dyn Store,
+                    mut items: &mut Vec<Rc<TodoItem>>) -> TodoResult<()> {
+    let selected = match try!(selected_items(opt, items, "Delete")) {
+        Some(selected)  => selected,
+        None            => return Ok(()),
+    };
+
+    let summary = summarize("Delete", &selected, "");
+    try!(change_items(opt, store, "delete", &summary, &selected, |item| {
+        try!(item_file::check_unchanged(store, item));
+        try!(store.delete(&item.filename));
+        println!("{}[{:3}]: {}: deleted", INDENT, item.id, item.heading);
+        Ok(())
+    }));
+    for item in &selected {
+        todo_items::remove_item_by_id(&mut items, item.id);
     }
+    Ok(())
 }
 
 
@@ -98,47 +238,292 @@ pub fn dump(items: &Vec<Rc<TodoItem>>) {
 }
 
 
-pub fn edit_item(items: &Vec<Rc<TodoItem>>,
-                 i: i32,
-                 editor: &Option<String>) -> TodoResult<()> {
+pub fn edit_item(opt: &Opt, store: &dyn Store, items: &Vec<Rc<TodoItem>>,
+                 i: i32)
+                 -> TodoResult<()> {
     if i == 0 {
         return Err(TodoError::new(TodoErrorKind::Other,
                                   "Item ID not set".to_string()));
     }
 
-    // TODO: improve error handling when editor is Some, but does not exist
-    let editor = match *editor {
-        Some(ref path)  => path,
-        None            => {
-            return Err(TodoError::new(TodoErrorKind::Other,
-                                      "Editor not set".to_string()));
+    // TODO: better error handling for the None case?
+    let item = match todo_items::get_item_by_id(&items, i) {
+        Some(item)  => item,
+        None        => {
+            print_err!("Error: cannot edit item {}: item not found", i);
+            return Ok(());
         },
     };
 
-    // TODO: better error handling for the None case?
-    match todo_items::get_item_by_id(&items, i) {
-        Some(item) => {
-            // run editor command for item
-            match Command::new(OsString::from(editor))
-                              .arg(&item.filename)
-                              .status() {
-                Ok(_)   => {},
-                Err(e)  => {
-                    print_err!("Error: editor exited with status {}",
-                               e);
-                },
-            };
+    // the file as it was read, to not overwrite changes made while editing
+    let mut read = (*item).clone();
+    read.stamp = store.stamp(&item.filename);
+    let original = try!(store.load(&item.filename));
+    let mut op = Op::new("edit", &format!("Edit '{}'", item.heading));
+    try!(op.touch(store, &item.filename));
+    let mut contents = original.clone();
+    loop {
+        contents = try!(edit_text(opt, &item.filename, &contents));
+        if check_edited(&item.filename, &contents) {
+            break;
+        }
+        match try!(ask_after_edit("(r)evert")) {
+            'e' => continue,
+            'r' => {
+                contents = original.clone();
+                println!("Reverted to the original.");
+            },
+            _   => println!("Kept the changes."),
+        };
+        break;
+    }
+
+    if contents != original {
+        let _lock = try!(store.lock());
+        if item_file::check_unchanged(store, &read).is_err() {
+            let path = try!(keep_text(&item.filename, &contents));
+            let err_msg = format!("'{}' has changed while it was edited, the \
+                                   edited item is in '{}'", item.filename,
+                                  path.display());
+            return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+        }
+        try!(store.save(&item.filename, &contents));
+        return op.finish(opt, store);
+    }
+    op.finish(opt, store)
+}
+
+
+/// Write `text` that could not be saved as item `name` to a temporary file,
+/// so that it is not lost, and return the path of the file.
+fn keep_text(name: &str, text: &str) -> TodoResult<PathBuf> {
+    let path = env::temp_dir().join(format!("todo-{}-{}.edited",
+                                            process::id(), name));
+    let mut fd = try!(OpenOptions::new().write(true).create_new(true)
+                                        .open(&path));
+    try!(fd.write_all(text.as_bytes()));
+    Ok(path)
+}
+
+
+/// Edit `text` in the editor and return the edited text. The text is
+/// edited in a temporary file named after `name`, so that the editor does
+/// not need to know how the store keeps the items.
+pub fn edit_text(opt: &Opt, name: &str, text: &str) -> TodoResult<String> {
+    let path = env::temp_dir().join(format!("todo-{}-{}", process::id(),
+                                            name));
+    // a file someone else put there is not written through
+    let mut fd = try!(OpenOptions::new().write(true).create_new(true)
+                                        .open(&path));
+    let result = fd.write_all(text.as_bytes())
+                   .map_err(From::from)
+                   .and_then(|_| run_editor(opt, &path))
+                   .and_then(|_| {
+                       let mut edited = String::new();
+                       try!(try!(File::open(&path)).read_to_string(&mut edited));
+                       Ok(edited)
+                   });
+    let _ = fs::remove_file(&path);
+    result
+}
+
+
+/// Run the editor on file `path`.
+fn run_editor(opt: &Opt, path: &Path) -> TodoResult<()> {
+    // the editor may come with arguments, like "code --wait"
+    let mut editor = match opt.editor {
+        Some(ref cmd)   => util::split_command(cmd),
+        None            => Vec::new(),
+    };
+    if editor.len() == 0 {
+        return Err(TodoError::new(TodoErrorKind::Other,
+                                  "Editor not set".to_string()));
+    }
+    let program = editor.remove(0);
+
+    match Command::new(&program).args(&editor).arg(path).status() {
+        Ok(ref status) if status.success()  => {},
+        Ok(status)  => print_err!("Error: editor exited with {}", status),
+        Err(e)      => {
+            let err_msg = format!("Cannot run editor '{}': {}", program, e);
+            return Err(TodoError::new(TodoErrorKind::Other, err_msg));
         },
-        None    =>
-            print_err!("Error: cannot edit item {}: item not found", i),
     };
+    Ok(())
+}
+
+
+/// Check the edited `contents` of item `name`, printing the errors found.
+fn check_edited(name: &str, contents: &str) -> bool {
+    let errors = todo_item::check_contents(contents);
+    if errors.len() > 0 {
+        print_err!("{} has errors:", name);
+        for err in &errors {
+            print_err!("{}{}", INDENT, err);
+        }
+    }
+    errors.len() == 0
+}
+
+
+/// Ask whether to edit an item with errors again ('e'), to undo the edit
+/// (the letter in parentheses in `undo`) or to keep it as it is ('k'). Keeps
+/// it when there is nothing to read the answer from.
+fn ask_after_edit(undo: &str) -> TodoResult<char> {
+    let undo_key = undo.chars().nth(1).unwrap_or('r');
+    loop {
+        let question = format!("(e)dit again, {} or (k)eep it anyway?", undo);
+        let answer = match try!(util::ask(&question)) {
+            Some(answer)    => answer,
+            None            => return Ok('k'),
+        };
+        match answer.chars().next() {
+            Some(c) if c == 'e' || c == undo_key || c == 'k' => return Ok(c),
+            _   => {},
+        };
+    }
+}
+
+
+/// Mark the selected items done.
+pub fn mark_done(opt: &Opt, store: &dyn Store, items: &Vec<Rc<TodoItem>>)
+                 -> TodoResult<()> {
+    let selected = match try!(selected_items(opt, items, "Mark done")) {
+        Some(selected)  => selected,
+        None            => return Ok(()),
+    };
+    // subtasks marked done at the same time do not count as open
+    for item in selected.iter().filter(|_| !opt.force) {
+        let open = todo_items::get_children(items, item).iter().filter(|c| {
+            c.status != Some(Status::Done) &&
+                !selected.iter().any(|s| s.id == c.id)
+        }).count();
+        if open > 0 {
+            let err_msg = format!("'{}' has {} open subtasks, mark them done \
+                                   first or use --force", item.heading, open);
+            return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+        }
+    }
+
+    let summary = summarize("Mark", &selected, " done");
+    let attrs = [Attr::new("status", "done")];
+    change_items(opt, store, "done", &summary, &selected, |item| {
+        try!(item_file::set_attrs(store, item, &attrs));
+        println!("{}[{:3}]: {}: done", INDENT, item.id, item.heading);
+        Ok(())
+    })
+}
+
 
+pub fn month(opt: &Opt, items: &Vec<Rc<TodoItem>>) -> TodoResult<()> {
+    let today = opt.clock.today();
+    let today_str = try!(util::date_to_str(&today));
+    let first = util::add_days(&today, 1 - today.tm_mday as i64);
+
+    let mut count = 0;
+    while util::add_days(&first, count).tm_mon == first.tm_mon {
+        count += 1;
+    }
+
+    let undone = todo_items::get_undone_items(&items);
+    let days = try!(calendar::get_days(&undone, &first, count, &today_str));
+    calendar::print_month(opt, &days, &today_str, util::terminal_width());
+    Ok(())
+}
+
+
+/// Write a new item from template `name` in the editor, and add it to the
+/// todo dir if it was changed.
+pub fn new_item(opt: &Opt, store: &dyn Store, name: Option<&str>)
+                -> TodoResult<()> {
+    let template = try!(template::render(opt, store, name));
+    let contents = match try!(edit_new_item(opt, &template)) {
+        Some(contents)  => contents,
+        None            => return Ok(()),
+    };
+
+    let heading = contents.lines().next().unwrap_or("").trim().to_string();
+    let mut op = Op::new("new", &format!("New '{}'", heading));
+    let key = try!(save_new_item(store, &mut op, &heading, &contents));
+    try!(op.finish(opt, store));
+    println!("Added {}", key);
     Ok(())
 }
 
 
-fn get_date_today_str() -> Result<String, time::ParseError> {
-    util::date_to_str(&time::now())
+/// Edit the new item from `template` until it is fine or the user wants to
+/// keep it anyway, and return its contents. None means that it was left
+/// unchanged or discarded.
+fn edit_new_item(opt: &Opt, template: &str) -> TodoResult<Option<String>> {
+    let mut contents = template.to_string();
+    loop {
+        contents = try!(edit_text(opt, "new", &contents));
+        if contents == template {
+            println!("No changes, discarded the new item.");
+            return Ok(None);
+        }
+        if check_edited("The new item", &contents) {
+            return Ok(Some(contents));
+        }
+        match try!(ask_after_edit("(d)iscard")) {
+            'e' => continue,
+            'd' => {
+                println!("Discarded the new item.");
+                return Ok(None);
+            },
+            _   => return Ok(Some(contents)),
+        };
+    }
+}
+
+
+/// Add a new item with `contents` to `store` as part of `op`, named after
+/// its heading, and return its key. The name is only taken if it is still
+/// free when the item is written, so that todo processes adding items at
+/// the same time do not overwrite each other's items.
+pub fn save_new_item(store: &dyn Store, op: &mut Op, heading: &str,
+                     contents: &str) -> TodoResult<String> {
+    // names found taken only when writing, which may not be items at all
+    let mut taken: Vec<String> = Vec::new();
+    loop {
+        let key = try!(new_item_key(store, heading, &taken));
+        if try!(store.create(&key, contents)) {
+            op.created(&key);
+            return Ok(key);
+        }
+        taken.push(key);
+    }
+}
+
+
+/// Name a new item after its heading, like "buy-milk", adding a number if
+/// the name is taken by an item or is in `taken`.
+fn new_item_key(store: &dyn Store, heading: &str, taken: &[String])
+                -> TodoResult<String> {
+    let mut name = String::new();
+    for c in heading.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            name.push(c);
+        } else if name.len() > 0 && !name.ends_with('-') {
+            name.push('-');
+        }
+        if name.chars().count() >= MAX_NAME_LEN {
+            break;
+        }
+    }
+    let name = match name.trim_matches('-') {
+        ""      => "item".to_string(),
+        name    => name.to_string(),
+    };
+
+    let keys = try!(store.list());
+    let mut key = store.new_key(&name);
+    let mut n = 2;
+    while keys.contains(&key) || taken.contains(&key) {
+        key = store.new_key(&format!("{}-{}", name, n));
+        n += 1;
+    }
+    Ok(key)
 }
 
 
@@ -148,13 +533,186 @@ pub fn print_help(program: &str, opts: &Options) {
 }
 
 
-fn print_item(item: &TodoItem) {
-    println!("\t[{:3}]: {}", item.id, item.heading);
+/// Move `item` to `date`, bumping its postponed counter. Only the `date` and
+/// `postponed` attrs of the file are rewritten.
+fn move_item(store: &dyn Store, item: &TodoItem, date: &Tm, dry_run: bool)
+             -> TodoResult<()> {
+    let date_str = try!(util::date_to_str(date));
+    let old_str = item.get_date_str().unwrap_or("no date".to_string());
+
+    if old_str == date_str {
+        println!("{}[{:3}]: {}: already on {}", INDENT, item.id,
+                 item.heading, date_str);
+        return Ok(());
+    }
+
+    println!("{}[{:3}]: {}: {} -> {}", INDENT, item.id, item.heading,
+             old_str, date_str);
+    if !dry_run {
+        try!(reschedule_item(store, item, &date_str));
+    }
+    Ok(())
+}
+
+
+/// Set the date of `item` to `date_str` and bump its postponed counter.
+pub fn reschedule_item(store: &dyn Store, item: &TodoItem, date_str: &str)
+                       -> TodoResult<()> {
+    let attrs = [Attr::new("date", date_str),
+                 Attr::new("postponed", &(item.postponed + 1).to_string())];
+    item_file::set_attrs(store, item, &attrs)
+}
+
+
+pub fn postpone(opt: &Opt, store: &dyn Store, items: &Vec<Rc<TodoItem>>)
+                -> TodoResult<()> {
+    if opt.dry_run {
+        println!("Would postpone:");
+        for item in try!(find_items(opt, items)) {
+            try!(move_item(store, &item, &try!(postpone_date(opt, &item)),
+                           true));
+        }
+        return Ok(());
+    }
+
+    let selected = match try!(selected_items(opt, items, "Postpone")) {
+        Some(selected)  => selected,
+        None            => return Ok(()),
+    };
+    let to = if selected.len() == 1 {
+        format!(" to {}",
+                try!(util::date_to_str(&try!(postpone_date(opt, &selected[0])))))
+    } else {
+        String::new()
+    };
+    let summary = summarize("Postpone", &selected, &to);
+    change_items(opt, store, "postpone", &summary, &selected, |item| {
+        move_item(store, item, &try!(postpone_date(opt, item)), false)
+    })
 }
 
 
-pub fn print_today(items: &Vec<Rc<TodoItem>>) {
-    let today_str = match get_date_today_str() {
+/// Get the date `item` is postponed to, as set with `--to` and `--workday`.
+pub fn postpone_date(opt: &Opt, item: &TodoItem) -> TodoResult<Tm> {
+    // relative dates count from the item's date, or from today if the item
+    // is already overdue, so postponing always moves the item forward
+    let today = opt.clock.today();
+    let base = match item.date {
+        Some(date) if date.to_timespec() > today.to_timespec() => date,
+        _   => today,
+    };
+
+    let to_arg = opt.postpone_to.clone().unwrap_or("+1".to_string());
+    let mut date = match util::parse_date_arg(&to_arg, &base) {
+        Ok(date)    => date,
+        Err(err)    => {
+            return Err(TodoError::new(TodoErrorKind::Other, err));
+        },
+    };
+    if opt.workday {
+        date = util::next_workday(&date);
+    }
+    Ok(date)
+}
+
+
+fn print_date(opt: &Opt, date_str: &str) {
+    println!("{}", color::paint(opt, &opt.theme.date, &format!("{}:", date_str)));
+}
+
+
+/// Print dated items under their dates, see `print_items`.
+fn print_by_date(opt: &Opt, dated: &Vec<Rc<TodoItem>>,
+                 items: &Vec<Rc<TodoItem>>) {
+    let mut day: Vec<Rc<TodoItem>> = Vec::new();
+    for item in dated {
+        if day.len() > 0 && day[0].get_date_str() != item.get_date_str() {
+            print_date(opt, &day[0].get_date_str().unwrap());
+            print_items(opt, &day, items);
+            println!("");
+            day.clear();
+        }
+        day.push(item.clone());
+    }
+    if day.len() > 0 {
+        print_date(opt, &day[0].get_date_str().unwrap());
+        print_items(opt, &day, items);
+    }
+}
+
+
+/// Print the items of a listing with the subtasks indented under their
+/// parents, and the progress of the parents counted from all `items`.
+pub fn print_items(opt: &Opt, group: &Vec<Rc<TodoItem>>,
+                   items: &Vec<Rc<TodoItem>>) {
+    for (item, depth) in todo_items::nest(group) {
+        let progress = todo_items::get_progress(items, &item);
+        print_entry(opt, &item, depth, progress);
+    }
+}
+
+
+/// Print a one line summary of the item. The heading is truncated to fit
+/// the terminal, and colored by its priority, date and status.
+pub fn print_item(opt: &Opt, item: &TodoItem) {
+    print_entry(opt, item, 0, (0, 0));
+}
+
+
+/// Print an item `depth` levels under its parent, with the number of its
+/// done subtasks and all of them in `progress`.
+fn print_entry(opt: &Opt, item: &TodoItem, depth: usize,
+               progress: (usize, usize)) {
+    let today_str = opt.clock.today_str().unwrap_or("".to_string());
+    let prefix = format!("{}[{:3}]: {}", INDENT, item.id,
+                         SUBTASK_INDENT.repeat(depth));
+    let mut suffix = match progress {
+        (_, 0)          => String::new(),
+        (done, total)   => format!(" ({}/{} done)", done, total),
+    };
+    if item.checklist.1 > 0 {
+        suffix.push_str(&format!(" ({}/{})", item.checklist.0,
+                                 item.checklist.1));
+    }
+    if item.status == Some(Status::Done) {
+        suffix.push_str(" (done)");
+    }
+
+    let mut heading = item.heading.clone();
+    if let Some(width) = util::output_width() {
+        let room = width.saturating_sub(prefix.len() + suffix.len());
+        heading = util::truncate(&heading, room);
+    }
+    heading.push_str(&suffix);
+
+    let style = item_style(opt, item, &today_str);
+    println!("{}{}", prefix, color::paint(opt, &style, &heading));
+}
+
+
+/// Get the style for an item: done items are dimmed, others are colored by
+/// their priority and highlighted if they are due today or overdue.
+pub fn item_style(opt: &Opt, item: &TodoItem, today_str: &str) -> Style {
+    let theme = &opt.theme;
+    let style = match item.priority {
+        Some(Priority::High)    => theme.high.clone(),
+        Some(Priority::Low)     => theme.low.clone(),
+        _                       => Style::default(),
+    };
+
+    if item.status == Some(Status::Done) {
+        return style.merge(&theme.done);
+    }
+    match item.get_date_str() {
+        Some(ref date) if &date[..] < today_str  => style.merge(&theme.overdue),
+        Some(ref date) if &date[..] == today_str => style.merge(&theme.today),
+        _   => style,
+    }
+}
+
+
+pub fn print_today(opt: &Opt, items: &Vec<Rc<TodoItem>>) {
+    let today_str = match opt.clock.today_str() {
         Ok(date)    => date,
         Err(err)    => {
             print_err!("Could not get today's date: {}", err);
@@ -163,49 +721,34 @@ pub fn print_today(items: &Vec<Rc<TodoItem>>) {
     };
 
     // get all undone items, and print past and today's items
-    let undone = todo_items::get_undone_items(&items);
+    let (undone, blocked) = unblocked(opt, &todo_items::get_undone_items(&items),
+                                      items);
     let before = todo_items::get_items_before(&undone, &today_str);
     let dateless = todo_items::get_dateless_items(&undone);
     let todays = todo_items::get_items_on_date(&undone, &today_str);
 
     println!("Items for today, {}", today_str);
     if todays.len() > 0 {
-        for item in todays {
-            print_item(&item);
-        }
+        print_items(opt, &todays, items);
     } else {
-        println!("\tNothing to do today :)");
+        println!("{}Nothing to do today :)", INDENT);
     }
 
     if before.len() > 0 {
         println!("\nPast unfinished tasks:");
-
-        let mut date_str: String = before.first().unwrap().get_date_str().unwrap();
-        println!("{}:", date_str);
-
-        // loop for different days
-        for item in before {
-            // update date if necessary
-            let date_tmp = item.get_date_str().unwrap();
-            if !date_str.eq(&date_tmp) {
-                date_str = date_tmp;
-                println!("\n{}:", date_str);
-            }
-            print_item(&item);
-        }
+        print_by_date(opt, &before, items);
     }
 
     if dateless.len() > 0 {
         println!("\nDateless unfinished tasks:");
-        for item in dateless {
-            print_item(&item);
-        }
+        print_items(opt, &dateless, items);
     }
+    print_blocked_count(blocked);
 }
 
 
-pub fn print_today_only(items: &Vec<Rc<TodoItem>>) {
-    let today_str = match get_date_today_str() {
+pub fn print_today_only(opt: &Opt, items: &Vec<Rc<TodoItem>>) {
+    let today_str = match opt.clock.today_str() {
         Ok(date)    => date,
         Err(err)    => {
             print_err!("Could not get today's date: {}", err);
@@ -214,16 +757,192 @@ pub fn print_today_only(items: &Vec<Rc<TodoItem>>) {
     };
 
     let todays_all = todo_items::get_items_on_date(&items, &today_str);
-    let todays = todo_items::get_undone_items(&todays_all);
+    let (todays, blocked) = unblocked(opt,
+                                      &todo_items::get_undone_items(&todays_all),
+                                      items);
 
     println!("Items for today, {}", today_str);
     if todays.len() > 0 {
-        for item in todays {
-            print_item(&item);
+        print_items(opt, &todays, items);
+    } else {
+        println!("{}Nothing to do today :)", INDENT);
+    }
+    print_blocked_count(blocked);
+}
+
+
+/// Leave out the items of `undone` that wait for other items, unless
+/// `--with-blocked` was given, and count them.
+fn unblocked(opt: &Opt, undone: &Vec<Rc<TodoItem>>, items: &Vec<Rc<TodoItem>>)
+             -> (Vec<Rc<TodoItem>>, usize) {
+    if opt.with_blocked {
+        return (undone.clone(), 0);
+    }
+    // the items of a cycle wait for each other, and are all left out
+    if let Err(e) = depends::check_cycles(items) {
+        print_err!("Error: {}", e);
+    }
+    let ready: Vec<Rc<TodoItem>> = undone.iter()
+        .filter(|item| !depends::is_blocked(items, item))
+        .cloned()
+        .collect();
+    let blocked = undone.len() - ready.len();
+    (ready, blocked)
+}
+
+
+fn print_blocked_count(blocked: usize) {
+    if blocked > 0 {
+        println!("\n{} blocked items not shown, see --blocked", blocked);
+    }
+}
+
+
+pub fn rollover(opt: &Opt, store: &dyn Store, items: &Vec<Rc<TodoItem>>)
+                -> TodoResult<()> {
+    let today = opt.clock.today();
+    let today_str = try!(util::date_to_str(&today));
+    let date = if opt.workday { util::next_workday(&today) } else { today };
+
+    let undone = todo_items::get_undone_items(&items);
+    let before = todo_items::get_items_before(&undone, &today_str);
+
+    if before.len() == 0 {
+        println!("No past unfinished tasks to roll over.");
+        return Ok(());
+    }
+
+    // other todo processes must not move the items at the same time
+    let _lock = if opt.dry_run { None } else { Some(try!(store.lock())) };
+    if opt.dry_run {
+        println!("Would roll over {} items:", before.len());
+    } else {
+        println!("Rolling over {} items:", before.len());
+    }
+    let summary = format!("Roll over {} items to {}", before.len(),
+                          try!(util::date_to_str(&date)));
+    let mut op = Op::new("rollover", &summary);
+    for item in &before {
+        if !opt.dry_run {
+            try!(op.touch(store, &item.filename));
+        }
+        try!(move_item(store, &item, &date, opt.dry_run));
+    }
+
+    if opt.dry_run {
+        return Ok(());
+    }
+    op.finish(opt, store)
+}
+
+
+/// Add tag `tag` to the selected items.
+pub fn tag_items(opt: &Opt, store: &dyn Store, items: &Vec<Rc<TodoItem>>,
+                 tag: &str) -> TodoResult<()> {
+    if tag.len() == 0 || tag.contains(',') || tag.contains('\n') {
+        let err_msg = format!("Invalid tag '{}'", tag);
+        return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+    }
+    let selected = match try!(selected_items(opt, items, "Tag")) {
+        Some(selected)  => selected,
+        None            => return Ok(()),
+    };
+
+    let summary = summarize("Tag", &selected, &format!(" '{}'", tag));
+    change_items(opt, store, "tag", &summary, &selected, |item| {
+        let mut tags: Vec<&str> = item.attrs.iter()
+            .filter(|attr| attr.key == todo_items::TAGS_ATTR)
+            .flat_map(|attr| attr.value.split(','))
+            .map(|t| t.trim())
+            .filter(|t| t.len() > 0)
+            .collect();
+        if tags.contains(&tag) {
+            println!("{}[{:3}]: {}: already tagged", INDENT, item.id,
+                     item.heading);
+            return Ok(());
         }
+        tags.push(tag);
+        let attrs = [Attr::new(todo_items::TAGS_ATTR, &tags.join(", "))];
+        try!(item_file::set_attrs(store, item, &attrs));
+        println!("{}[{:3}]: {}: tagged {}", INDENT, item.id, item.heading, tag);
+        Ok(())
+    })
+}
+
+
+/// Get the items selected with `-i` or the action, in the order given.
+pub fn find_items(opt: &Opt, items: &Vec<Rc<TodoItem>>)
+                  -> TodoResult<Vec<Rc<TodoItem>>> {
+    if opt.item_ids.len() == 0 {
+        return Err(TodoError::new(TodoErrorKind::Other,
+                                  "Item ID not set".to_string()));
+    }
+    let mut found = Vec::new();
+    for &id in &opt.item_ids {
+        match todo_items::get_item_by_id(items, id) {
+            Some(item)  => found.push(item),
+            None        => {
+                let err_msg = format!("Item {} not found", id);
+                return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+            },
+        };
+    }
+    Ok(found)
+}
+
+
+/// Get the selected items, asking the user to confirm `verb` first when
+/// there are many of them, unless `--yes` was given. None means that the
+/// user said no.
+fn selected_items(opt: &Opt, items: &Vec<Rc<TodoItem>>, verb: &str)
+                  -> TodoResult<Option<Vec<Rc<TodoItem>>>> {
+    let selected = try!(find_items(opt, items));
+    if selected.len() <= CONFIRM_LIMIT || opt.yes {
+        return Ok(Some(selected));
+    }
+
+    println!("{} these {} items?", verb, selected.len());
+    for item in &selected {
+        print_item(opt, item);
+    }
+    match try!(util::ask("Continue? [y/N]")) {
+        Some(ref a) if a == "y" || a == "yes" => return Ok(Some(selected)),
+        _   => {},
+    };
+    println!("Nothing changed.");
+    Ok(None)
+}
+
+
+/// Describe a change to `items` for the journal, like "Mark 'Call Bob'
+/// done" or "Mark 3 items done".
+fn summarize(verb: &str, items: &[Rc<TodoItem>], rest: &str) -> String {
+    if items.len() == 1 {
+        format!("{} '{}'{}", verb, items[0].heading, rest)
     } else {
-        println!("\tNothing to do today :)");
+        format!("{} {} items{}", verb, items.len(), rest)
+    }
+}
+
+
+/// Make `change` to each of `items` as one change in the journal. The
+/// items changed before an error are still recorded.
+pub fn change_items<F>(opt: &Opt, store: &dyn Store, kind: &str,
+                       summary: &str, items: &[Rc<TodoItem>], mut change: F)
+                       -> TodoResult<()>
+        where F: FnMut(&TodoItem) -> TodoResult<()> {
+    // other todo processes must not change the items at the same time
+    let _lock = try!(store.lock());
+    let mut op = Op::new(kind, summary);
+    let mut result = Ok(());
+    for item in items {
+        result = op.touch(store, &item.filename).and_then(|_| change(item));
+        if result.is_err() {
+            break;
+        }
     }
+    try!(op.finish(opt, store));
+    result
 }
 
 
@@ -233,15 +952,47 @@ pub fn print_version() {
 }
 
 
+pub fn week(opt: &Opt, items: &Vec<Rc<TodoItem>>) -> TodoResult<()> {
+    let today = opt.clock.today();
+    let today_str = try!(util::date_to_str(&today));
+    let monday = calendar::get_monday(&today);
+    let sunday = util::add_days(&monday, 6);
+
+    let undone = todo_items::get_undone_items(&items);
+    let days = try!(calendar::get_days(&undone, &monday, 7, &today_str));
+
+    println!("Week {}, {} to {}:", try!(time::strftime("%V", &monday)),
+             try!(util::date_to_str(&monday)),
+             try!(util::date_to_str(&sunday)));
+    calendar::print_week(opt, &days, &today_str, util::terminal_width());
+    Ok(())
+}
+
+
 pub fn show_item(items: &Vec<Rc<TodoItem>>, i: i32) {
     // 0 indicates id was not set
     if i != 0 {
         match todo_items::get_item_by_id(&items, i) {
             Some(i) => {
                 println!("id: {}", i.id);
+                println!("stable id: {}", i.stable_id());
                 println!("filename: {}", i.filename);
                 println!("heading: {}", i.heading);
-                println!("\n{}", i.body);
+                if i.postponed > 0 {
+                    println!("postponed: {}", i.postponed);
+                }
+                for reference in links::references(&i) {
+                    match links::resolve(items, &reference) {
+                        Some(l) => println!("links to: [{:3}] {}", l.id,
+                                            l.heading),
+                        None    => println!("links to: '{}' (not found)",
+                                            reference),
+                    };
+                }
+                for l in links::get_backlinks(items, &i) {
+                    println!("referenced by: [{:3}] {}", l.id, l.heading);
+                }
+                println!("\n{}", i.body.as_ref().map_or("", |b| &b[..]));
             },
             None    => print_err!("Error: Item {} not found", i),
         };
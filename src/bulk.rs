@@ -0,0 +1,250 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use action;
+use attr::Attr;
+use error::TodoResult;
+use item_file;
+use journal::Op;
+use opt::Opt;
+use status::Status;
+use store::Store;
+use todo_item::TodoItem;
+use util;
+
+const HELP: &'static str = "\
+# One item per line: stable ID, date, status and heading. Change the date
+# (YYYY-MM-DD, or - for none), the status (todo or done) or the heading to
+# change the item. Remove a line to move the item to the trash. A line
+# without an ID, like \"2016-02-01 todo Buy milk\", is a new item where the
+# date and the status can be left out. Lines starting with '#' are ignored.
+";
+
+
+/// An item as a line in the bulk edit document.
+#[derive(PartialEq)]
+struct Line {
+    date:       Option<String>,
+    done:       bool,
+    heading:    String,
+    id:         Option<String>,
+}
+
+
+impl Line {
+    fn from_item(item: &TodoItem) -> Line {
+        Line {
+            date:       item.get_date_str(),
+            done:       item.status == Some(Status::Done),
+            heading:    item.heading.clone(),
+            id:         Some(item.stable_id()),
+        }
+    }
+
+
+    fn to_line(&self) -> String {
+        format!("{} {} {} {}", self.id.as_ref().map_or("", |id| &id[..]),
+                self.date.as_ref().map_or("-", |d| &d[..]),
+                if self.done { "done" } else { "todo" }, self.heading)
+            .trim_start().to_string()
+    }
+
+
+    /// Parse a line, with `ids` being the stable IDs of the edited items.
+    fn parse(text: &str, ids: &HashMap<String, Rc<TodoItem>>)
+             -> Result<Line, String> {
+        let mut rest = text.trim();
+        let next_word = |rest: &mut &str, ok: &dyn Fn(&str) -> bool| {
+            let word = rest.split_whitespace().next().unwrap_or("");
+            if word.len() > 0 && ok(word) {
+                *rest = rest[word.len()..].trim_start();
+                Some(word.to_string())
+            } else {
+                None
+            }
+        };
+
+        let id = next_word(&mut rest, &|w| ids.contains_key(w));
+        let date = next_word(&mut rest, &|w| {
+            w == "-" || util::str_to_date(w).is_ok()
+        });
+        let status = next_word(&mut rest, &|w| w == "todo" || w == "done");
+        if id.is_some() && (date.is_none() || status.is_none()) {
+            return Err(format!("'{}': expected ID, date, status and heading",
+                               text));
+        }
+        if rest.len() == 0 {
+            return Err(format!("'{}': the heading is missing", text));
+        }
+
+        Ok(Line {
+            date:       date.and_then(|d| if d == "-" { None } else { Some(d) }),
+            done:       status.map_or(false, |s| s == "done"),
+            heading:    rest.to_string(),
+            id:         id,
+        })
+    }
+}
+
+
+/// Edit the selected items together as lines of one document, and write the
+/// changes back to them.
+pub fn bulk_edit(opt: &Opt, store: &dyn Store, items: &Vec<Rc<TodoItem>>)
+                 -> TodoResult<()> {
+    let selected = try!(action::find_items(opt, items));
+    let ids: HashMap<String, Rc<TodoItem>> = selected.iter().map(|item| {
+        (item.stable_id(), item.clone())
+    }).collect();
+
+    let mut document = String::from(HELP);
+    for item in &selected {
+        document.push_str(&Line::from_item(item).to_line());
+        document.push('\n');
+    }
+
+    let lines = match try!(edit_document(opt, &document, &ids)) {
+        Some(lines) => lines,
+        None        => return Ok(()),
+    };
+
+    // the lock is not held while editing, the items are checked for changes
+    // made meanwhile as they are written
+    let _lock = try!(store.lock());
+    let mut op = Op::new("bulk-edit",
+                         &format!("Bulk edit {} items", selected.len()));
+    let result = apply(opt, store, &mut op, &selected, &ids, &lines);
+    try!(op.finish(opt, store));
+    result
+}
+
+
+/// Let the user edit the document until it parses, and return its lines.
+/// None means that nothing was changed or the user gave up.
+fn edit_document(opt: &Opt, document: &str,
+                 ids: &HashMap<String, Rc<TodoItem>>)
+                 -> TodoResult<Option<Vec<Line>>> {
+    let mut edited = document.to_string();
+    loop {
+        edited = try!(action::edit_text(opt, "bulk-edit", &edited));
+        if edited == document {
+            println!("No changes.");
+            return Ok(None);
+        }
+
+        let mut lines = Vec::new();
+        let mut errors = Vec::new();
+        for text in edited.lines() {
+            if text.trim().len() == 0 || text.trim_start().starts_with('#') {
+                continue;
+            }
+            match Line::parse(text, ids) {
+                Ok(line)    => lines.push(line),
+                Err(err)    => errors.push(err),
+            };
+        }
+        for (i, line) in lines.iter().enumerate() {
+            if line.id.is_some() && lines[..i].iter().any(|l| l.id == line.id) {
+                errors.push(format!("{} is on more than one line",
+                                    line.id.as_ref().unwrap()));
+            }
+        }
+        if errors.len() == 0 {
+            return Ok(Some(lines));
+        }
+
+        print_err!("The edited items have errors:");
+        for err in &errors {
+            print_err!("    {}", err);
+        }
+        let answer = try!(util::ask("(e)dit again or (a)bort?"));
+        if !answer.map_or(false, |a| a.starts_with('e')) {
+            println!("Nothing changed.");
+            return Ok(None);
+        }
+    }
+}
+
+
+fn apply(opt: &Opt, store: &dyn Store, op: &mut Op,
+         selected: &Vec<Rc<TodoItem>>, ids: &HashMap<String, Rc<TodoItem>>,
+         lines: &Vec<Line>) -> TodoResult<()> {
+    for line in lines {
+        let item = match line.id {
+            Some(ref id)    => &ids[id],
+            None            => {
+                try!(add_item(store, op, line));
+                continue;
+            },
+        };
+        let old = Line::from_item(item);
+        if *line == old {
+            continue;
+        }
+
+        let heading = if line.heading != old.heading { Some(&line.heading[..]) }
+                      else { None };
+        let mut attrs: Vec<Attr> = Vec::new();
+        let mut remove: Vec<&str> = Vec::new();
+        match line.date {
+            Some(ref date) if line.date != old.date => {
+                attrs.push(Attr::new("date", date));
+            },
+            None if old.date.is_some()  => remove.push("date"),
+            _                           => {},
+        };
+        if line.done != old.done {
+            attrs.push(Attr::new("status", if line.done { "done" }
+                                           else { "todo" }));
+        }
+        try!(op.touch(store, &item.filename));
+        try!(item_file::edit_header(store, item, heading, &attrs, &remove));
+        println!("Changed [{}] {}", item.id, line.heading);
+    }
+
+    let removed: Vec<&Rc<TodoItem>> = selected.iter().filter(|item| {
+        let id = Some(item.stable_id());
+        !lines.iter().any(|line| line.id == id)
+    }).collect();
+    if removed.len() == 0 {
+        return Ok(());
+    }
+    println!("Removed lines:");
+    for item in &removed {
+        action::print_item(opt, item);
+    }
+    if !opt.yes {
+        let answer = try!(util::ask("Move these items to the trash? [y/N]"));
+        if !answer.map_or(false, |a| a.starts_with('y')) {
+            return Ok(());
+        }
+    }
+    for item in removed {
+        try!(item_file::check_unchanged(store, item));
+        try!(op.touch(store, &item.filename));
+        match try!(store.trash(&item.filename)) {
+            Some(key)   => println!("Moved [{}] to {}", item.id, key),
+            None        => println!("Deleted [{}]", item.id),
+        };
+    }
+    Ok(())
+}
+
+
+fn add_item(store: &dyn Store, op: &mut Op, line: &Line) -> TodoResult<()> {
+    let mut contents = format!("{}\n", line.heading);
+    if let Some(ref date) = line.date {
+        contents.push_str(&format!("date: {}\n", date));
+    }
+    if line.done {
+        contents.push_str("status: done\n");
+    }
+
+    let key = try!(action::save_new_item(store, op, &line.heading,
+                                         &contents));
+    println!("Added {}", key);
+    Ok(())
+}
+
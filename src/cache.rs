@@ -0,0 +1,255 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use attr::Attr;
+use error::TodoResult;
+use store::Stamp;
+use todo_item::TodoItem;
+use util;
+
+const MAGIC: &'static [u8] = b"todo-cache";
+// bump when the layout below changes, older caches are then thrown away
+const FORMAT_VERSION: u32 = 2;
+
+
+/// Parsed item headers and the checklist counts of the bodies, kept in a
+/// file between runs. An entry is only used while the stamp of its item
+/// stays the same. The date, status and other typed fields are parsed from
+/// the cached attrs, which is cheap next to reading the files.
+///
+/// The file is the magic, the format version and the entries, followed by a
+/// checksum of all that. A cache that can not be read is thrown away and
+/// built again.
+pub struct Cache {
+    dirty:      bool,
+    entries:    HashMap<String, Entry>,
+    path:       PathBuf,
+}
+
+
+struct Entry {
+    attrs:      Vec<Attr>,
+    checklist:  (usize, usize),
+    heading:    String,
+    stamp:      Stamp,
+}
+
+
+impl Cache {
+    /// Read the cache at `path`. A missing, old or corrupt cache gives an
+    /// empty one.
+    pub fn open(path: &Path) -> Cache {
+        let mut cache = Cache {
+            dirty:      false,
+            entries:    HashMap::new(),
+            path:       path.to_path_buf(),
+        };
+
+        let mut data = Vec::new();
+        match File::open(path).and_then(|mut fd| fd.read_to_end(&mut data)) {
+            Ok(_)   => {},
+            Err(ref e) if e.kind() == ErrorKind::NotFound => { return cache; },
+            Err(e)  => {
+                print_err!("Could not read cache '{}': {}", path.display(), e);
+                cache.dirty = true;
+                return cache;
+            },
+        };
+
+        match decode(&data) {
+            Some(entries)   => cache.entries = entries,
+            // rewritten on the next save
+            None            => cache.dirty = true,
+        };
+        cache
+    }
+
+
+    /// Get the cached item `key` with the given ID, if it is still fresh.
+    pub fn get(&self, key: &str, stamp: &Stamp, id: i32) -> Option<TodoItem> {
+        match self.entries.get(key) {
+            Some(entry) if entry.stamp == *stamp => {
+                let mut item = TodoItem::new_from_parts(key, id,
+                                                        entry.heading.clone(),
+                                                        entry.attrs.clone(),
+                                                        None);
+                item.stamp = Some(*stamp);
+                item.checklist = entry.checklist;
+                Some(item)
+            },
+            _   => None,
+        }
+    }
+
+
+    /// Cache the header of `item`, read when the item had `stamp`.
+    pub fn insert(&mut self, stamp: Stamp, item: &TodoItem) {
+        self.entries.insert(item.filename.clone(), Entry {
+            attrs:      item.attrs.clone(),
+            checklist:  item.checklist,
+            heading:    item.heading.clone(),
+            stamp:      stamp,
+        });
+        self.dirty = true;
+    }
+
+
+    /// Forget the items that are not in `keys` anymore.
+    pub fn retain(&mut self, keys: &[String]) {
+        let keys: HashSet<&str> = keys.iter().map(|k| &k[..]).collect();
+        let count = self.entries.len();
+        self.entries.retain(|key, _| keys.contains(&key[..]));
+        self.dirty |= self.entries.len() != count;
+    }
+
+
+    /// Write the cache if it has changed. The new cache is written next to
+    /// the old one and renamed over it, so readers never see half of it. The
+    /// temporary file is named after the process, as another todo may be
+    /// writing the cache at the same time.
+    pub fn save(&mut self) -> TodoResult<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(format!(".tmp{}", process::id()));
+        let tmp = PathBuf::from(tmp);
+        {
+            let mut fd = try!(File::create(&tmp));
+            try!(fd.write_all(&encode(&self.entries)));
+        }
+        try!(fs::rename(&tmp, &self.path));
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+
+fn encode(entries: &HashMap<String, Entry>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(MAGIC);
+    put_u32(&mut buf, FORMAT_VERSION);
+    put_u32(&mut buf, entries.len() as u32);
+    for (key, entry) in entries {
+        put_str(&mut buf, key);
+        buf.extend_from_slice(&entry.stamp.mtime.to_le_bytes());
+        put_u64(&mut buf, entry.stamp.size);
+        put_u64(&mut buf, entry.stamp.inode);
+        put_str(&mut buf, &entry.heading);
+        put_u32(&mut buf, entry.checklist.0 as u32);
+        put_u32(&mut buf, entry.checklist.1 as u32);
+        put_u32(&mut buf, entry.attrs.len() as u32);
+        for attr in &entry.attrs {
+            put_str(&mut buf, &attr.key);
+            put_str(&mut buf, &attr.value);
+        }
+    }
+    let sum = util::fnv1a(&buf);
+    put_u64(&mut buf, sum);
+    buf
+}
+
+
+fn decode(data: &[u8]) -> Option<HashMap<String, Entry>> {
+    if data.len() < MAGIC.len() + 8 || !data.starts_with(MAGIC) {
+        return None;
+    }
+    let (body, sum) = data.split_at(data.len() - 8);
+    if util::fnv1a(body) != u64::from_le_bytes(sum.try_into().unwrap()) {
+        return None;
+    }
+
+    let mut reader = Reader { data: body, pos: MAGIC.len() };
+    if try_opt!(reader.u32()) != FORMAT_VERSION {
+        return None;
+    }
+
+    let mut entries = HashMap::new();
+    for _ in 0..try_opt!(reader.u32()) {
+        let key = try_opt!(reader.str());
+        let stamp = Stamp {
+            mtime:  try_opt!(reader.u64()) as i64,
+            size:   try_opt!(reader.u64()),
+            inode:  try_opt!(reader.u64()),
+        };
+        let heading = try_opt!(reader.str());
+        let checklist = (try_opt!(reader.u32()) as usize,
+                         try_opt!(reader.u32()) as usize);
+        let mut attrs = Vec::new();
+        for _ in 0..try_opt!(reader.u32()) {
+            let key = try_opt!(reader.str());
+            let value = try_opt!(reader.str());
+            attrs.push(Attr { key: key, value: value });
+        }
+        entries.insert(key, Entry {
+            attrs:      attrs,
+            checklist:  checklist,
+            heading:    heading,
+            stamp:      stamp,
+        });
+    }
+
+    if reader.pos != body.len() {
+        return None;
+    }
+    Some(entries)
+}
+
+
+fn put_u32(buf: &mut Vec<u8>, val: u32) {
+    buf.extend_from_slice(&val.to_le_bytes());
+}
+
+
+fn put_u64(buf: &mut Vec<u8>, val: u64) {
+    buf.extend_from_slice(&val.to_le_bytes());
+}
+
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    put_u32(buf, s.len() as u32);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+
+struct Reader<'a> {
+    data:   &'a [u8],
+    pos:    usize,
+}
+
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() - self.pos < len {
+            return None;
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Some(bytes)
+    }
+
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes(8).map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+    }
+
+
+    fn str(&mut self) -> Option<String> {
+        let len = try_opt!(self.u32()) as usize;
+        let bytes = try_opt!(self.bytes(len));
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
@@ -0,0 +1,196 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::rc::Rc;
+
+use time;
+use time::Tm;
+
+use action;
+use color::{self, Style};
+use error::TodoResult;
+use opt::Opt;
+use todo_item::TodoItem;
+use todo_items;
+use util;
+
+// narrowest week grid column that is still readable
+const MIN_WEEK_COL_WIDTH: usize = 8;
+// width of the week number column in the month grid
+const WEEK_NUM_WIDTH: usize = 4;
+const DAY_NAMES: [&'static str; 7] =
+    ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+
+/// A single calendar day and the undone items on it.
+pub struct Day {
+    pub date:       Tm,
+    pub date_str:   String,
+    pub items:      Vec<Rc<TodoItem>>,
+    pub overdue:    bool,
+}
+
+
+/// Collect `count` days starting from `start`, with the undone items of each
+/// day. Days before `today_str` with items are marked overdue.
+pub fn get_days(items: &Vec<Rc<TodoItem>>, start: &Tm, count: i64,
+                today_str: &str) -> TodoResult<Vec<Day>> {
+    let mut days = Vec::new();
+    for i in 0..count {
+        let date = util::add_days(start, i);
+        let date_str = try!(util::date_to_str(&date));
+        let day_items = todo_items::get_items_on_date(items, &date_str);
+        let overdue = &date_str[..] < today_str && day_items.len() > 0;
+        days.push(Day {
+            date:       date,
+            date_str:   date_str,
+            items:      day_items,
+            overdue:    overdue,
+        });
+    }
+    Ok(days)
+}
+
+
+/// Return the Monday on or before `date`.
+pub fn get_monday(date: &Tm) -> Tm {
+    let date = util::add_days(date, 0);
+    util::add_days(&date, -(weekday_index(&date) as i64))
+}
+
+
+/// Index of the weekday of `date`, starting from Monday.
+fn weekday_index(date: &Tm) -> usize {
+    ((date.tm_wday + 6) % 7) as usize
+}
+
+
+fn iso_week(date: &Tm) -> String {
+    time::strftime("%V", date).unwrap_or("".to_string())
+}
+
+
+/// Print a month grid in the style of cal(1). Days with items show the item
+/// count and days with overdue items are marked with '!'. If the terminal
+/// is too narrow for the counts only a '*' marker is shown.
+pub fn print_month(opt: &Opt, days: &[Day], today_str: &str, width: usize) {
+    let first = match days.first() {
+        Some(day)   => day,
+        None        => return,
+    };
+
+    // cell is "DD(NN)!" or "DD*<" and a separating space
+    let wide = WEEK_NUM_WIDTH + 7 * 8 <= width;
+    let cell_width = if wide { 7 } else { 4 };
+
+    let title = time::strftime("%B %Y", &first.date).unwrap();
+    println!("{}", color::paint(opt, &opt.theme.date, &title));
+
+    let mut header = util::pad("Wk", WEEK_NUM_WIDTH);
+    for name in DAY_NAMES.iter() {
+        header.push_str(&util::pad(&name[..2], cell_width));
+        header.push(' ');
+    }
+    println!("{}", header.trim_end());
+
+    let lead = weekday_index(&first.date);
+    let mut line = util::pad(&iso_week(&first.date), WEEK_NUM_WIDTH);
+    for _ in 0..lead {
+        line.push_str(&util::pad("", cell_width + 1));
+    }
+
+    for (i, day) in days.iter().enumerate() {
+        let mut cell = format!("{:2}", day.date.tm_mday);
+        if day.items.len() > 0 {
+            if wide {
+                cell.push_str(&format!("({})", day.items.len()));
+            } else if !day.overdue {
+                cell.push('*');
+            }
+        }
+        if day.overdue {
+            cell.push('!');
+        }
+        let mut style = Style::default();
+        if day.overdue {
+            style = opt.theme.overdue.clone();
+        } else if day.date_str == today_str {
+            cell.push('<');
+            style = opt.theme.today.clone();
+        }
+        line.push_str(&color::paint(opt, &style, &util::pad(&cell, cell_width)));
+        line.push(' ');
+
+        if (lead + i) % 7 == 6 {
+            println!("{}", line.trim_end());
+            if i + 1 < days.len() {
+                line = util::pad(&iso_week(&days[i + 1].date), WEEK_NUM_WIDTH);
+            } else {
+                line = String::new();
+            }
+        }
+    }
+    if line.trim().len() > 0 {
+        println!("{}", line.trim_end());
+    }
+
+    if wide {
+        println!("\n(N) number of items, ! overdue items, < today");
+    } else {
+        println!("\n* items, ! overdue items, < today");
+    }
+}
+
+
+/// Print the days as seven columns with the items under each day. Falls back
+/// to a list of days if the terminal is too narrow for the columns.
+pub fn print_week(opt: &Opt, days: &[Day], today_str: &str, width: usize) {
+    let col_width = width.saturating_sub(6) / 7;
+
+    if col_width < MIN_WEEK_COL_WIDTH {
+        for day in days {
+            let today_mark = if day.date_str == today_str { " (today)" }
+                             else { "" };
+            println!("{} {}{}:", DAY_NAMES[weekday_index(&day.date)],
+                     day.date_str, today_mark);
+            for item in day.items.iter() {
+                action::print_item(opt, item);
+            }
+        }
+        return;
+    }
+
+    let mut header: Vec<String> = Vec::new();
+    let mut rule: Vec<String> = Vec::new();
+    for day in days {
+        let mut name = format!("{} {}", DAY_NAMES[weekday_index(&day.date)],
+                               day.date.tm_mday);
+        let mut style = opt.theme.date.clone();
+        if day.date_str == today_str {
+            name.push_str(" <");
+            style = style.merge(&opt.theme.today);
+        }
+        header.push(color::paint(opt, &style, &util::pad(&name, col_width)));
+        rule.push((0..col_width).map(|_| '-').collect());
+    }
+    println!("{}", header.join("|").trim_end());
+    println!("{}", rule.join("+"));
+
+    let rows = days.iter().map(|d| d.items.len()).max().unwrap_or(0);
+    for row in 0..rows {
+        let mut cells: Vec<String> = Vec::new();
+        for day in days {
+            let cell = match day.items.get(row) {
+                Some(item)  => {
+                    let mark = if day.overdue { "!" } else { "" };
+                    let text = format!("{}[{}] {}", mark, item.id, item.heading);
+                    let style = action::item_style(opt, item, today_str);
+                    color::paint(opt, &style, &util::pad(&text, col_width))
+                },
+                None        => util::pad("", col_width),
+            };
+            cells.push(cell);
+        }
+        println!("{}", cells.join("|").trim_end());
+    }
+}
@@ -0,0 +1,81 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::io::{self, Read};
+
+use time::Tm;
+
+use action;
+use error::{TodoError, TodoErrorKind, TodoResult};
+use journal::Op;
+use mail::Mail;
+use opt::Opt;
+use store::Store;
+use util;
+
+
+/// Add an item from the text piped to todo. The first line is the heading
+/// and the rest is the body.
+pub fn capture(opt: &Opt, store: &dyn Store) -> TodoResult<()> {
+    let text = try!(read_stdin());
+    let mut lines = text.lines().skip_while(|l| l.trim().len() == 0);
+    let heading = match lines.next() {
+        Some(line)  => line.trim().to_string(),
+        None        => {
+            return Err(TodoError::new(TodoErrorKind::Other,
+                                      "Nothing to capture".to_string()));
+        },
+    };
+    let body: Vec<&str> = lines.collect();
+
+    add_item(opt, store, &heading, &opt.clock.today(), "stdin",
+             &body.join("\n"))
+}
+
+
+/// Add an item from the email message piped to todo. The subject is the
+/// heading, the date of the message the date and its text the body.
+pub fn capture_mail(opt: &Opt, store: &dyn Store) -> TodoResult<()> {
+    let mail = Mail::parse(&try!(read_stdin()));
+    let heading = match mail.header("subject") {
+        Some(ref subject) if subject.trim().len() > 0 => subject.trim().to_string(),
+        _   => "(no subject)".to_string(),
+    };
+    // a date given with --date wins over the one in the message
+    let date = match mail.date() {
+        Some(date) if !opt.clock.is_fixed() => date,
+        _                                   => opt.clock.today(),
+    };
+    let source = match mail.header("message-id") {
+        Some(id)    => format!("mail {}", id),
+        None        => "mail".to_string(),
+    };
+
+    add_item(opt, store, &heading, &date, &source, &mail.text())
+}
+
+
+fn read_stdin() -> TodoResult<String> {
+    let mut bytes = Vec::new();
+    try!(io::stdin().read_to_end(&mut bytes));
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+
+fn add_item(opt: &Opt, store: &dyn Store, heading: &str, date: &Tm,
+            source: &str, body: &str) -> TodoResult<()> {
+    let mut contents = format!("{}\ndate: {}\nsource: {}\n", heading,
+                               try!(util::date_to_str(date)),
+                               source.replace('\n', " "));
+    let body = body.trim_matches('\n').trim_end();
+    if body.len() > 0 {
+        contents.push_str(&format!("\n{}\n", body));
+    }
+
+    let mut op = Op::new("capture", &format!("Capture '{}'", heading));
+    let key = try!(action::save_new_item(store, &mut op, heading,
+                                         &contents));
+    try!(op.finish(opt, store));
+    println!("Added {}", key);
+    Ok(())
+}
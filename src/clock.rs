@@ -0,0 +1,56 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use time;
+use time::Tm;
+
+use util;
+
+/// Source of "today" for all date dependent actions. Normally this follows
+/// the system clock, but it can be fixed to a certain date with `--date` or
+/// the TODO_NOW environment variable.
+#[derive(Clone,Debug)]
+pub struct Clock {
+    fixed:  Option<Tm>,
+}
+
+
+impl Clock {
+    pub fn fixed(date: &Tm) -> Clock {
+        Clock {
+            fixed:  Some(util::add_days(date, 0)),
+        }
+    }
+
+
+    pub fn is_fixed(&self) -> bool {
+        self.fixed.is_some()
+    }
+
+
+    pub fn system() -> Clock {
+        Clock {
+            fixed:  None,
+        }
+    }
+
+
+    /// Return today's date at midnight UTC, so that day arithmetic does not
+    /// shift it across a date boundary.
+    pub fn today(&self) -> Tm {
+        if let Some(date) = self.fixed {
+            return date;
+        }
+
+        let now = time::now();
+        match util::date_to_str(&now).map(|s| util::str_to_date(&s)) {
+            Ok(Ok(date))    => util::add_days(&date, 0),
+            _               => now,
+        }
+    }
+
+
+    pub fn today_str(&self) -> Result<String, time::ParseError> {
+        util::date_to_str(&self.today())
+    }
+}
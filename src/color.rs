@@ -0,0 +1,211 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::env;
+use std::io::{self, IsTerminal};
+
+use opt::Opt;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum ColorMode {
+    Always,
+    Auto,
+    Never,
+}
+
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+
+/// How a piece of output is drawn on a color terminal.
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct Style {
+    pub bold:       bool,
+    pub dim:        bool,
+    pub fg:         Option<Color>,
+    pub underline:  bool,
+}
+
+
+/// Styles for the different kinds of output.
+#[derive(Clone,Debug)]
+pub struct Theme {
+    pub date:       Style,
+    pub done:       Style,
+    pub high:       Style,
+    pub low:        Style,
+    pub matched:    Style,
+    pub overdue:    Style,
+    pub today:      Style,
+}
+
+
+impl Style {
+    pub fn fg(color: Color) -> Style {
+        Style { fg: Some(color), ..Style::default() }
+    }
+
+
+    fn escape(&self) -> String {
+        let mut codes: Vec<String> = Vec::new();
+        if self.bold { codes.push("1".to_string()); }
+        if self.dim { codes.push("2".to_string()); }
+        if self.underline { codes.push("4".to_string()); }
+        if let Some(color) = self.fg {
+            codes.push((30 + color as u8).to_string());
+        }
+        format!("\x1b[{}m", codes.join(";"))
+    }
+
+
+    pub fn is_plain(&self) -> bool {
+        *self == Style::default()
+    }
+
+
+    /// Combine two styles, the color of `other` taking precedence.
+    pub fn merge(&self, other: &Style) -> Style {
+        Style {
+            bold:       self.bold || other.bold,
+            dim:        self.dim || other.dim,
+            fg:         other.fg.or(self.fg),
+            underline:  self.underline || other.underline,
+        }
+    }
+
+
+    /// Parse a style from a list of words, e.g. "red bold". "none" is a
+    /// plain style.
+    pub fn parse(spec: &str) -> Option<Style> {
+        let mut style = Style::default();
+        for word in spec.split_whitespace() {
+            match word {
+                "bold"      => style.bold = true,
+                "dim"       => style.dim = true,
+                "underline" => style.underline = true,
+                "none"      => {},
+                _           => match parse_color(word) {
+                    Some(color) => style.fg = Some(color),
+                    None        => return None,
+                },
+            }
+        }
+        Some(style)
+    }
+}
+
+
+impl Theme {
+    /// Get one of the built in themes: "default", "light" or "mono".
+    pub fn by_name(name: &str) -> Option<Theme> {
+        match name {
+            "default"   => Some(Theme {
+                date:       Style { bold: true, ..Style::default() },
+                done:       Style { dim: true, ..Style::default() },
+                high:       Style::fg(Color::Yellow),
+                low:        Style::fg(Color::Cyan),
+                matched:    Style { bold: true, ..Style::fg(Color::Green) },
+                overdue:    Style::fg(Color::Red),
+                today:      Style { bold: true, ..Style::default() },
+            }),
+            "light"     => Some(Theme {
+                date:       Style { underline: true, ..Style::default() },
+                done:       Style { dim: true, ..Style::default() },
+                high:       Style::fg(Color::Magenta),
+                low:        Style::fg(Color::Blue),
+                matched:    Style { bold: true, ..Style::fg(Color::Green) },
+                overdue:    Style { bold: true, ..Style::fg(Color::Red) },
+                today:      Style { bold: true, ..Style::default() },
+            }),
+            "mono"      => Some(Theme {
+                date:       Style { bold: true, ..Style::default() },
+                done:       Style { dim: true, ..Style::default() },
+                high:       Style { bold: true, ..Style::default() },
+                low:        Style::default(),
+                matched:    Style { bold: true, underline: true,
+                                    ..Style::default() },
+                overdue:    Style { underline: true, ..Style::default() },
+                today:      Style { bold: true, ..Style::default() },
+            }),
+            _           => None,
+        }
+    }
+
+
+    /// Override the style used for `role`. Returns false for unknown roles.
+    pub fn set_style(&mut self, role: &str, style: Style) -> bool {
+        match role {
+            "date"      => self.date = style,
+            "done"      => self.done = style,
+            "high"      => self.high = style,
+            "low"       => self.low = style,
+            "match"     => self.matched = style,
+            "overdue"   => self.overdue = style,
+            "today"     => self.today = style,
+            _           => return false,
+        }
+        true
+    }
+}
+
+
+/// Return `text` drawn with `style`, if colors are in use.
+pub fn paint(opt: &Opt, style: &Style, text: &str) -> String {
+    if opt.color != ColorMode::Always || style.is_plain() {
+        return text.to_string();
+    }
+    format!("{}{}\x1b[0m", style.escape(), text)
+}
+
+
+fn parse_color(val: &str) -> Option<Color> {
+    match val {
+        "black"     => Some(Color::Black),
+        "red"       => Some(Color::Red),
+        "green"     => Some(Color::Green),
+        "yellow"    => Some(Color::Yellow),
+        "blue"      => Some(Color::Blue),
+        "magenta"   => Some(Color::Magenta),
+        "cyan"      => Some(Color::Cyan),
+        "white"     => Some(Color::White),
+        _           => None,
+    }
+}
+
+
+pub fn parse_color_mode(val: &str) -> Option<ColorMode> {
+    match val {
+        "always"    => Some(ColorMode::Always),
+        "auto"      => Some(ColorMode::Auto),
+        "never"     => Some(ColorMode::Never),
+        _           => None,
+    }
+}
+
+
+/// Decide between Always and Never for the Auto mode: colors are used when
+/// stdout is a terminal and NO_COLOR is not set.
+pub fn resolve_color_mode(mode: ColorMode) -> ColorMode {
+    match mode {
+        ColorMode::Auto => {
+            let no_color = env::var("NO_COLOR").map(|v| v.len() > 0)
+                                               .unwrap_or(false);
+            if !no_color && io::stdout().is_terminal() {
+                ColorMode::Always
+            } else {
+                ColorMode::Never
+            }
+        },
+        mode            => mode,
+    }
+}
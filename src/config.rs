@@ -0,0 +1,108 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::io::{Error, ErrorKind};
+
+use attr::Attr;
+use color::{self, Style, Theme};
+use opt::Opt;
+use store::Store;
+
+const CONFIG_FILE: &'static str = ".config";
+
+
+/// Read settings from the `.config` file in `store`, if there is one. The
+/// file has one "key: value" pair per line, like the attrs of an item.
+/// Empty lines and lines starting with '#' are ignored.
+pub fn read_config(opt: &mut Opt, store: &dyn Store) -> Result<(), Error> {
+    let contents = match store.load_meta(CONFIG_FILE) {
+        Ok(Some(contents))  => contents,
+        Ok(None)            => return Ok(()),
+        Err(e)              => {
+            return Err(Error::new(ErrorKind::Other, e.to_string()));
+        },
+    };
+
+    // the theme is set first so that single styles can override it
+    let mut attrs: Vec<Attr> = Vec::new();
+    for (lineno, line) in (1..).zip(contents.lines()) {
+        if line.trim().len() == 0 || line.trim().starts_with('#') {
+            continue;
+        }
+        match Attr::new_from_line(line) {
+            Ok(attr)    => attrs.push(attr),
+            Err(err)    => {
+                let err_msg = format!("{}:{}: {}", CONFIG_FILE, lineno, err);
+                return Err(Error::new(ErrorKind::Other, err_msg));
+            },
+        };
+    }
+    attrs.sort_by_key(|attr| attr.key != "theme");
+
+    for attr in attrs {
+        try!(set_config_value(opt, &attr).map_err(|e| {
+            let err_msg = format!("{}: {}", CONFIG_FILE, e);
+            Error::new(ErrorKind::Other, err_msg)
+        }));
+    }
+    Ok(())
+}
+
+
+fn set_config_value(opt: &mut Opt, attr: &Attr) -> Result<(), String> {
+    match &attr.key[..] {
+        "color"     => {
+            match color::parse_color_mode(&attr.value) {
+                Some(mode)  => opt.color = mode,
+                None        => {
+                    return Err(format!("invalid color mode '{}'",
+                                       attr.value));
+                },
+            };
+        },
+        "git"       => {
+            opt.git = match &attr.value[..] {
+                "yes"   => true,
+                "no"    => false,
+                value   => {
+                    return Err(format!("invalid git setting '{}', use yes \
+                                       or no", value));
+                },
+            };
+        },
+        "index"     => {
+            opt.index = match &attr.value[..] {
+                "none"      => false,
+                "sqlite" if cfg!(feature = "sqlite") => true,
+                "sqlite"    => {
+                    return Err("todo was built without SQLite support"
+                               .to_string());
+                },
+                value       => {
+                    return Err(format!("unknown index '{}'", value));
+                },
+            };
+        },
+        "theme"     => {
+            match Theme::by_name(&attr.value) {
+                Some(theme) => opt.theme = theme,
+                None        => {
+                    return Err(format!("unknown theme '{}'", attr.value));
+                },
+            };
+        },
+        key if key.starts_with("color-") => {
+            let style = match Style::parse(&attr.value) {
+                Some(style) => style,
+                None        => {
+                    return Err(format!("invalid style '{}'", attr.value));
+                },
+            };
+            if !opt.theme.set_style(&key["color-".len()..], style) {
+                return Err(format!("unknown style '{}'", key));
+            }
+        },
+        key         => { return Err(format!("unknown setting '{}'", key)); },
+    };
+    Ok(())
+}
@@ -0,0 +1,186 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::rc::Rc;
+
+use action;
+use error::{TodoError, TodoErrorKind, TodoResult};
+use opt::Opt;
+use status::Status;
+use todo_item::TodoItem;
+use todo_items;
+
+const INDENT: &'static str = "               ";
+
+
+/// Get the references in the `depends` attrs of `item`, in order.
+fn references(item: &TodoItem) -> Vec<&str> {
+    item.attrs.iter()
+        .filter(|attr| attr.key == todo_items::DEPENDS_ATTR)
+        .flat_map(|attr| attr.value.split(','))
+        .map(|r| r.trim())
+        .filter(|r| r.len() > 0)
+        .collect()
+}
+
+
+/// Get the items `item` depends on. References to items that are not there,
+/// like deleted ones, are left out.
+pub fn get_dependencies(items: &Vec<Rc<TodoItem>>, item: &TodoItem)
+                        -> Vec<Rc<TodoItem>> {
+    let mut deps: Vec<Rc<TodoItem>> = Vec::new();
+    for reference in references(item) {
+        let dep = items.iter().find(|i| todo_items::refers_to(reference, i));
+        if let Some(dep) = dep {
+            if !deps.iter().any(|d| d.id == dep.id) {
+                deps.push(dep.clone());
+            }
+        }
+    }
+    deps
+}
+
+
+/// Get the dependencies of `item` that are not done yet.
+pub fn get_open_dependencies(items: &Vec<Rc<TodoItem>>, item: &TodoItem)
+                             -> Vec<Rc<TodoItem>> {
+    get_dependencies(items, item).into_iter()
+                                 .filter(|d| d.status != Some(Status::Done))
+                                 .collect()
+}
+
+
+/// Check whether `item` waits for items that are not done.
+pub fn is_blocked(items: &Vec<Rc<TodoItem>>, item: &TodoItem) -> bool {
+    get_open_dependencies(items, item).len() > 0
+}
+
+
+/// Check that no item depends on itself through other items. The error
+/// names the items of the first cycle found.
+pub fn check_cycles(items: &Vec<Rc<TodoItem>>) -> TodoResult<()> {
+    // ids of the items known to be outside any cycle
+    let mut checked: Vec<i32> = Vec::new();
+    for item in items {
+        let mut path: Vec<Rc<TodoItem>> = Vec::new();
+        if let Some(cycle) = find_cycle(items, item, &mut path, &mut checked) {
+            let names: Vec<String> = cycle.iter().map(|i| {
+                format!("'{}'", i.heading)
+            }).collect();
+            let err_msg = format!("Dependency cycle: {}", names.join(" -> "));
+            return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+        }
+    }
+    Ok(())
+}
+
+
+/// Follow the dependencies from `item` depth first, with `path` the items
+/// on the way to it. Returns the cycle, starting and ending with the same
+/// item, if one is found.
+fn find_cycle(items: &Vec<Rc<TodoItem>>, item: &Rc<TodoItem>,
+              path: &mut Vec<Rc<TodoItem>>, checked: &mut Vec<i32>)
+              -> Option<Vec<Rc<TodoItem>>> {
+    if let Some(i) = path.iter().position(|p| p.id == item.id) {
+        let mut cycle = path[i..].to_vec();
+        cycle.push(item.clone());
+        return Some(cycle);
+    }
+    if checked.contains(&item.id) {
+        return None;
+    }
+
+    path.push(item.clone());
+    for dep in get_dependencies(items, item) {
+        if let Some(cycle) = find_cycle(items, &dep, path, checked) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+    checked.push(item.id);
+    None
+}
+
+
+/// Print the undone items that do not wait for anything.
+pub fn ready(opt: &Opt, items: &Vec<Rc<TodoItem>>) -> TodoResult<()> {
+    try!(check_cycles(items));
+    let ready: Vec<Rc<TodoItem>> =
+        todo_items::get_undone_items(items).into_iter()
+                                           .filter(|i| !is_blocked(items, i))
+                                           .collect();
+    if ready.len() == 0 {
+        println!("Nothing is ready to be done.");
+        return Ok(());
+    }
+    println!("Ready to be done:");
+    action::print_items(opt, &ready, items);
+    Ok(())
+}
+
+
+/// Print the undone items that wait for other items, and what they wait
+/// for.
+pub fn blocked(opt: &Opt, items: &Vec<Rc<TodoItem>>) -> TodoResult<()> {
+    try!(check_cycles(items));
+    let mut found = false;
+    for item in todo_items::get_undone_items(items) {
+        let open = get_open_dependencies(items, &item);
+        if open.len() == 0 {
+            continue;
+        }
+        if !found {
+            println!("Blocked:");
+            found = true;
+        }
+        action::print_item(opt, &item);
+        for dep in open {
+            println!("{}waits for [{}] {}", INDENT, dep.id, dep.heading);
+        }
+    }
+    if !found {
+        println!("Nothing is blocked.");
+    }
+    Ok(())
+}
+
+
+/// Print the dependencies between the items as a Graphviz DOT graph, with
+/// an edge from every item to the ones waiting for it. Items without
+/// dependencies in either direction are left out, and done ones are gray.
+pub fn graph(items: &Vec<Rc<TodoItem>>) -> TodoResult<()> {
+    try!(check_cycles(items));
+    let mut edges: Vec<(Rc<TodoItem>, Rc<TodoItem>)> = Vec::new();
+    for item in items {
+        for dep in get_dependencies(items, item) {
+            edges.push((dep, item.clone()));
+        }
+    }
+
+    println!("digraph todo {{");
+    println!("    rankdir=LR;");
+    for item in items {
+        if !edges.iter().any(|&(ref a, ref b)| a.id == item.id ||
+                                               b.id == item.id) {
+            continue;
+        }
+        let style = if item.status == Some(Status::Done) {
+            ", color=gray, fontcolor=gray"
+        } else {
+            ""
+        };
+        println!("    \"{}\" [label=\"{}\"{}];", item.stable_id(),
+                 escape(&item.heading), style);
+    }
+    for &(ref from, ref to) in &edges {
+        println!("    \"{}\" -> \"{}\";", from.stable_id(), to.stable_id());
+    }
+    println!("}}");
+    Ok(())
+}
+
+
+/// Escape `text` for a quoted DOT string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
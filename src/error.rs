@@ -5,6 +5,10 @@ use std::error::Error;
 use std::io;
 use std::fmt;
 
+#[cfg(feature = "sqlite")]
+use rusqlite;
+use time;
+
 pub type TodoResult<T> = Result<T, TodoError>;
 
 pub struct TodoError {
@@ -42,3 +46,20 @@ impl From<io::Error> for TodoError {
                     message: err.description().to_string() }
     }
 }
+
+
+impl From<time::ParseError> for TodoError {
+    fn from(err: time::ParseError) -> TodoError {
+        TodoError { kind: TodoErrorKind::Parse,
+                    message: format!("{}", err) }
+    }
+}
+
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for TodoError {
+    fn from(err: rusqlite::Error) -> TodoError {
+        TodoError { kind: TodoErrorKind::Other,
+                    message: format!("{}", err) }
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::collections::HashSet;
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+
+use error::{TodoError, TodoErrorKind, TodoResult};
+use opt::Opt;
+use todo_item::TodoItem;
+use todo_items;
+
+// trailers that mark the commits made by todo, and the ones reverting them
+// before there was a journal
+const ACTION_TRAILER: &'static str = "Todo-Action:";
+const UNDO_TRAILER: &'static str = "Todo-Undo:";
+
+
+/// Run git with `args` in the todo dir and return what it printed.
+fn run(opt: &Opt, args: &[&str]) -> TodoResult<String> {
+    let out = try!(Command::new("git").arg("-C").arg(&opt.todo_dir)
+                                      .args(args)
+                                      .stdin(Stdio::null())
+                                      .output());
+    if !out.status.success() {
+        let err_msg = format!("git {} failed: {}", args[0],
+                              String::from_utf8_lossy(&out.stderr).trim());
+        return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+
+/// Commit the changes `action` made to the files in `paths`, if git mode is
+/// on. Other changes in the todo dir are left out of the commit. Relative
+/// paths are relative to the todo dir, as git is run there.
+pub fn commit(opt: &Opt, action: &str, summary: &str, paths: &[&str])
+              -> TodoResult<()> {
+    if !opt.git {
+        return Ok(());
+    }
+
+    let mut changed: Vec<&str> = Vec::new();
+    for &path in paths {
+        if opt.todo_dir.join(path).exists() {
+            try!(run(opt, &["add", "--", path]));
+        } else if try!(run(opt, &["ls-files", "--", path])).len() > 0 {
+            try!(run(opt, &["rm", "-q", "--cached", "--", path]));
+        } else {
+            // never committed and already gone
+            continue;
+        }
+        changed.push(path);
+    }
+
+    // e.g. the editor was closed without saving
+    let mut args = vec!["diff", "--cached", "--quiet", "--"];
+    args.extend(changed.iter().cloned());
+    if changed.len() == 0 || run(opt, &args).is_ok() {
+        return Ok(());
+    }
+
+    let message = format!("{}\n\n{} {}\n", summary, ACTION_TRAILER, action);
+    let mut args = vec!["commit", "-q", "-m", &message, "--"];
+    args.extend(changed.iter().cloned());
+    run(opt, &args).map(|_| ())
+}
+
+
+/// Print the commits that changed item `id`.
+pub fn history(opt: &Opt, items: &Vec<Rc<TodoItem>>, id: i32)
+               -> TodoResult<()> {
+    let item = match todo_items::get_item_by_id(items, id) {
+        Some(item)  => item,
+        None        => {
+            let err_msg = format!("Item {} not found", id);
+            return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+        },
+    };
+
+    let log = try!(run(opt, &["log", "--follow", "--date=short",
+                              "--format=%h %ad %an: %s", "--",
+                              &item.filename]));
+    if log.len() == 0 {
+        println!("No history for item {}.", id);
+    } else {
+        println!("History of [{}] {}:", id, item.heading);
+        print!("{}", log);
+    }
+    Ok(())
+}
+
+
+
+/// Revert the last commit made by todo that has not been undone yet. This
+/// is how changes were undone before the journal, and is still used when
+/// the journal is empty.
+pub fn undo(opt: &Opt) -> TodoResult<()> {
+    let log = try!(run(opt, &["log", "-z", "--format=%H%n%s%n%b",
+                              "--extended-regexp",
+                              "--grep=^(Todo-Action|Todo-Undo):"]));
+    // newest first, so undos are seen before the commits they undid
+    let mut undone: HashSet<&str> = HashSet::new();
+    let mut target: Option<(&str, &str)> = None;
+    for entry in log.split('\0').filter(|e| e.trim().len() > 0) {
+        let mut lines = entry.trim_start_matches('\n').lines();
+        let hash = lines.next().unwrap_or("");
+        let subject = lines.next().unwrap_or("");
+        let mut is_action = false;
+        for line in lines {
+            if line.starts_with(UNDO_TRAILER) {
+                undone.insert(line[UNDO_TRAILER.len()..].trim());
+            } else if line.starts_with(ACTION_TRAILER) {
+                is_action = true;
+            }
+        }
+        if is_action && !undone.contains(hash) {
+            target = Some((hash, subject));
+            break;
+        }
+    }
+
+    let (hash, subject) = match target {
+        Some(target)    => target,
+        None            => {
+            return Err(TodoError::new(TodoErrorKind::Other,
+                                      "Nothing to undo".to_string()));
+        },
+    };
+
+    if let Err(e) = run(opt, &["revert", "--no-commit", hash]) {
+        let _ = run(opt, &["revert", "--abort"]);
+        return Err(e);
+    }
+    let message = format!("Undo: {}\n\n{} {}\n", subject, UNDO_TRAILER, hash);
+    try!(run(opt, &["commit", "-q", "-m", &message]));
+    println!("Undid: {}", subject);
+    Ok(())
+}
@@ -0,0 +1,371 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use rusqlite::{self, Connection, Row, Transaction};
+use rusqlite::types::Value;
+
+use action::{self, Action};
+use attr::Attr;
+use error::TodoResult;
+use opt::Opt;
+use status::Status;
+use store::{DirStore, Stamp, Store};
+use todo_item::TodoItem;
+use todo_items;
+use util;
+
+const INDEX_FILE: &'static str = ".index.sqlite";
+// bump when the tables change, the index is then built again from scratch
+const SCHEMA_VERSION: i32 = 3;
+const SCHEMA: &'static str = "
+    CREATE TABLE items (
+        key         TEXT PRIMARY KEY,
+        mtime       INTEGER NOT NULL,
+        size        INTEGER NOT NULL,
+        inode       INTEGER NOT NULL,
+        heading     TEXT NOT NULL,
+        body        TEXT NOT NULL,
+        date        TEXT,
+        done        INTEGER NOT NULL,
+        status      TEXT
+    );
+    CREATE TABLE attrs (
+        key         TEXT NOT NULL,
+        name        TEXT NOT NULL,
+        value       TEXT NOT NULL
+    );
+    CREATE TABLE tags (
+        key         TEXT NOT NULL,
+        tag         TEXT NOT NULL
+    );
+    CREATE INDEX items_date ON items (date);
+    CREATE INDEX items_done_date ON items (done, date);
+    CREATE INDEX attrs_key ON attrs (key);
+    CREATE INDEX attrs_name_value ON attrs (name, value COLLATE NOCASE);
+    CREATE INDEX tags_tag ON tags (tag);
+";
+
+
+/// Which items to load from the index. Dates are YYYY-MM-DD strings.
+pub struct Query {
+    /// Only items dated on or after this.
+    pub from:       Option<String>,
+    /// Only items dated on or before this.
+    pub to:         Option<String>,
+    /// Also items without a date.
+    pub dateless:   bool,
+    /// Leave out done items.
+    pub undone:     bool,
+    /// Only items that match one of these "key=value" filters, see
+    /// todo_items::select_items.
+    pub filters:    Vec<Attr>,
+    /// Also all subtasks, for the progress of their parents.
+    pub children:   bool,
+}
+
+
+impl Query {
+    pub fn all() -> Query {
+        Query {
+            from:       None,
+            to:         None,
+            dateless:   true,
+            undone:     false,
+            filters:    Vec::new(),
+            children:   false,
+        }
+    }
+}
+
+
+/// An SQLite index of the items in a DirStore. The files stay the source of
+/// truth: an item is parsed again whenever the modification time, size or
+/// inode of its file differs from the indexed one.
+pub struct Index {
+    conn:       Connection,
+}
+
+
+impl Index {
+    /// Open the index of `store`, creating it if needed.
+    pub fn open(store: &DirStore) -> TodoResult<Index> {
+        let conn = try!(Connection::open(store.dir().join(INDEX_FILE)));
+        let version: i32 = try!(conn.query_row("PRAGMA user_version", [],
+                                               |row| row.get(0)));
+        if version != SCHEMA_VERSION {
+            try!(conn.execute_batch("DROP TABLE IF EXISTS items;
+                                     DROP TABLE IF EXISTS attrs;
+                                     DROP TABLE IF EXISTS tags;"));
+            try!(conn.execute_batch(SCHEMA));
+            try!(conn.execute_batch(&format!("PRAGMA user_version = {}",
+                                             SCHEMA_VERSION)));
+        }
+        Ok(Index { conn: conn })
+    }
+
+
+    /// Bring the index up to date with `store`, and return the keys of all
+    /// items in the order IDs are given in.
+    pub fn sync(&mut self, store: &DirStore) -> TodoResult<Vec<String>> {
+        let keys = try!(store.list());
+        let tx = try!(self.conn.transaction());
+
+        let mut indexed: HashMap<String, Stamp> = HashMap::new();
+        {
+            let mut stmt = try!(tx.prepare("SELECT key, mtime, size, inode \
+                                            FROM items"));
+            let rows = try!(stmt.query_map([], |row| {
+                Ok((try!(row.get(0)), try!(stamp_from_row(row, 1))))
+            }));
+            for row in rows {
+                let (key, stat) = try!(row);
+                indexed.insert(key, stat);
+            }
+        }
+
+        for key in &keys {
+            let stamp = match store.stamp(key) {
+                Some(stamp) => stamp,
+                None        => { continue; },
+            };
+            if indexed.remove(key) == Some(stamp) {
+                continue;
+            }
+
+            let parsed = store.load(key)
+                              .and_then(|c| TodoItem::new_from_str(key, &c, 0));
+            try!(remove_item(&tx, key));
+            match parsed {
+                Ok(item)    => try!(insert_item(&tx, &item, &stamp)),
+                Err(err)    => print_err!("Could not load todo file '{}': {}",
+                                          key, err),
+            };
+        }
+
+        // whatever was not listed is gone from the store
+        for key in indexed.keys() {
+            try!(remove_item(&tx, key));
+        }
+        try!(tx.commit());
+        Ok(keys)
+    }
+
+
+    /// Check whether any item has attr `name`.
+    pub fn has_attr(&self, name: &str) -> TodoResult<bool> {
+        let count: i64 = try!(self.conn.query_row(
+            "SELECT COUNT(*) FROM attrs WHERE name = ?", [name],
+            |row| row.get(0)));
+        Ok(count > 0)
+    }
+
+
+    /// Load the items matching `query`. `keys` is the list from sync, it
+    /// gives the IDs of the items.
+    pub fn load(&self, keys: &[String], query: &Query)
+                -> TodoResult<Vec<Rc<TodoItem>>> {
+        let ids: HashMap<&str, i32> = keys.iter().map(|k| &k[..])
+                                          .zip(1..).collect();
+
+        let mut sql = "SELECT key, heading, body, mtime, size, inode FROM items \
+                       WHERE 1".to_string();
+        let mut params: Vec<Value> = Vec::new();
+        if query.undone {
+            sql.push_str(" AND done = 0");
+        }
+        let mut dated = "date IS NOT NULL".to_string();
+        if let Some(ref from) = query.from {
+            dated.push_str(" AND date >= ?");
+            params.push(Value::Text(from.clone()));
+        }
+        if let Some(ref to) = query.to {
+            dated.push_str(" AND date <= ?");
+            params.push(Value::Text(to.clone()));
+        }
+        if query.dateless {
+            sql.push_str(&format!(" AND (({}) OR date IS NULL)", dated));
+        } else {
+            sql.push_str(&format!(" AND {}", dated));
+        }
+        if query.filters.len() > 0 {
+            let mut any: Vec<&str> = Vec::new();
+            for filter in &query.filters {
+                any.push(filter_sql(filter, &mut params));
+            }
+            sql.push_str(&format!(" AND ({})", any.join(" OR ")));
+        }
+        if query.children {
+            // AND binds tighter, so this adds to all of the above
+            sql.push_str(" OR key IN (SELECT key FROM attrs WHERE name = ?)");
+            params.push(Value::Text(todo_items::PARENT_ATTR.to_string()));
+        }
+
+        let mut attr_stmt = try!(self.conn.prepare(
+            "SELECT name, value FROM attrs WHERE key = ? ORDER BY rowid"));
+        let mut stmt = try!(self.conn.prepare(&sql));
+        let rows = try!(stmt.query_map(rusqlite::params_from_iter(params),
+                                       |row| {
+            Ok((try!(row.get::<_, String>(0)), try!(row.get(1)),
+                try!(row.get(2)), try!(stamp_from_row(row, 3))))
+        }));
+
+        let mut items: Vec<Rc<TodoItem>> = Vec::new();
+        for row in rows {
+            let (key, heading, body, stamp) = try!(row);
+            let id = match ids.get(&key[..]) {
+                Some(id)    => *id,
+                None        => { continue; },
+            };
+            let attrs = try!(attr_stmt.query_map([&key], |row| {
+                Ok(Attr { key: try!(row.get(0)), value: try!(row.get(1)) })
+            }));
+            let attrs = try!(attrs.collect::<Result<Vec<Attr>, _>>());
+            let mut item = TodoItem::new_from_parts(&key, id, heading, attrs,
+                                                    Some(body));
+            item.stamp = Some(stamp);
+            items.push(Rc::new(item));
+        }
+
+        // same order as todo_items::get_todo_items gives
+        items.sort_by_key(|item| item.id);
+        items.sort();
+        Ok(items)
+    }
+}
+
+
+fn insert_item(tx: &Transaction, item: &TodoItem, stamp: &Stamp)
+               -> TodoResult<()> {
+    let status = match item.status {
+        Some(Status::Done)  => Some("done"),
+        Some(Status::Todo)  => Some("todo"),
+        None                => None,
+    };
+    try!(tx.execute("INSERT INTO items (key, mtime, size, inode, heading, \
+                     body, date, done, status) \
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                    (&item.filename, stamp.mtime, stamp.size as i64,
+                     stamp.inode as i64, &item.heading,
+                     item.body.as_ref().map_or("", |b| &b[..]),
+                     item.get_date_str(),
+                     item.status == Some(Status::Done), status)));
+    for attr in &item.attrs {
+        try!(tx.execute("INSERT INTO attrs (key, name, value) VALUES (?, ?, ?)",
+                        (&item.filename, &attr.key, &attr.value)));
+        if attr.key == todo_items::TAGS_ATTR {
+            for tag in attr.value.split(',').map(|t| t.trim()) {
+                try!(tx.execute("INSERT INTO tags (key, tag) VALUES (?, ?)",
+                                (&item.filename, tag)));
+            }
+        }
+    }
+    Ok(())
+}
+
+
+/// Get the SQL condition for the items `filter` selects, pushing its
+/// parameters to `params`. Like in todo_items::select_items, values other
+/// than tags are matched in any case and items without a status are to be
+/// done.
+fn filter_sql(filter: &Attr, params: &mut Vec<Value>) -> &'static str {
+    let value = Value::Text(filter.value.clone());
+    if filter.key == todo_items::TAGS_ATTR {
+        params.push(value);
+        "key IN (SELECT key FROM tags WHERE tag = ?)"
+    } else if filter.key == "status" {
+        params.push(value);
+        if filter.value == "todo" {
+            "(status IS NULL OR key IN (SELECT key FROM attrs \
+              WHERE name = 'status' AND value = ? COLLATE NOCASE))"
+        } else {
+            "(status IS NOT NULL AND key IN (SELECT key FROM attrs \
+              WHERE name = 'status' AND value = ? COLLATE NOCASE))"
+        }
+    } else {
+        params.push(Value::Text(filter.key.clone()));
+        params.push(value);
+        "key IN (SELECT key FROM attrs WHERE name = ? \
+                 AND value = ? COLLATE NOCASE)"
+    }
+}
+
+
+/// Read a stamp from the mtime, size and inode columns starting at `col`.
+fn stamp_from_row(row: &Row, col: usize) -> rusqlite::Result<Stamp> {
+    Ok(Stamp {
+        mtime:  try!(row.get(col)),
+        size:   try!(row.get::<_, i64>(col + 1)) as u64,
+        inode:  try!(row.get::<_, i64>(col + 2)) as u64,
+    })
+}
+
+
+fn remove_item(tx: &Transaction, key: &str) -> TodoResult<()> {
+    try!(tx.execute("DELETE FROM items WHERE key = ?", [key]));
+    try!(tx.execute("DELETE FROM attrs WHERE key = ?", [key]));
+    try!(tx.execute("DELETE FROM tags WHERE key = ?", [key]));
+    Ok(())
+}
+
+
+/// Get the query for the items `view` shows. The views still do their own
+/// filtering, the query only has to include everything they need.
+fn view_query(opt: &Opt, view: &Action) -> TodoResult<Query> {
+    let today_str = try!(opt.clock.today_str());
+    let mut query = Query::all();
+    query.children = true;
+    match *view {
+        Action::Agenda      => {
+            let (from, to, _) = try!(action::agenda_range(opt));
+            query.from = Some(try!(util::date_to_str(&from)));
+            query.to = Some(try!(util::date_to_str(&to)));
+            query.dateless = false;
+        },
+        Action::Month | Action::Week => {
+            query.dateless = false;
+            query.undone = true;
+        },
+        Action::Rollover    => {
+            query.to = Some(today_str);
+            query.dateless = false;
+            query.undone = true;
+        },
+        Action::Today       => {
+            query.to = Some(today_str);
+            query.undone = true;
+        },
+        Action::TodayOnly   => {
+            query.from = Some(today_str.clone());
+            query.to = Some(today_str);
+            query.dateless = false;
+            query.undone = true;
+        },
+        // actions on the items selected with filters only need those
+        Action::BulkEdit | Action::Delete | Action::Done | Action::Postpone |
+        Action::Tag         => {
+            if let Some(ref selector) = opt.selector {
+                query.filters = todo_items::selector_filters(selector)
+                                           .unwrap_or(Vec::new());
+            }
+        },
+        _                   => {},
+    };
+    Ok(query)
+}
+
+
+/// Get the items `view` needs through the index of `store`. Watching needs
+/// all items, as any of them can change into view. So do dependencies, as
+/// an item is blocked by items outside the view too.
+pub fn get_todo_items(opt: &Opt, store: &DirStore, view: &Action)
+                      -> TodoResult<Vec<Rc<TodoItem>>> {
+    let mut index = try!(Index::open(store));
+    let keys = try!(index.sync(store));
+    let all = opt.watch || try!(index.has_attr(todo_items::DEPENDS_ATTR));
+    let query = if all { Query::all() }
+                else { try!(view_query(opt, view)) };
+    index.load(&keys, &query)
+}
@@ -0,0 +1,155 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use attr::Attr;
+use error::{TodoError, TodoErrorKind, TodoResult};
+use store::Store;
+use todo_item::{self, Step, TodoItem};
+
+
+/// Set the given attrs in `item` in `store`. Existing attr lines with
+/// the same key are replaced in place, new ones are appended to the end of
+/// the header section. The heading, other attrs and the body are written
+/// back untouched.
+pub fn set_attrs(store: &dyn Store, item: &TodoItem, attrs: &[Attr])
+                 -> TodoResult<()> {
+    edit_header(store, item, None, attrs, &[])
+}
+
+
+/// Change the header of `item` in `store`: replace the heading if one is
+/// given, set `attrs` as `set_attrs` does and remove the attrs with the keys
+/// in `remove`.
+pub fn edit_header(store: &dyn Store, item: &TodoItem, heading: Option<&str>,
+                   attrs: &[Attr], remove: &[&str]) -> TodoResult<()> {
+    try!(check_unchanged(store, item));
+    let contents = try!(store.load(&item.filename));
+    let mut new_contents = try!(replace_attrs(&contents, attrs));
+    if remove.len() > 0 {
+        new_contents = remove_attrs(&new_contents, remove);
+    }
+    if let Some(heading) = heading {
+        new_contents = replace_heading(&new_contents, heading);
+    }
+    store.save(&item.filename, &new_contents)
+}
+
+
+/// Check or uncheck step `n`, counting from 1, of the checklist in the body
+/// of `item` in `store`, and return the step as it was. Only the mark of
+/// the step is changed.
+pub fn toggle_step(store: &dyn Store, item: &TodoItem, n: usize)
+                   -> TodoResult<Step> {
+    try!(check_unchanged(store, item));
+    let contents = try!(store.load(&item.filename));
+    let mut lines: Vec<String> = contents.split('\n')
+                                         .map(|l| l.to_string())
+                                         .collect();
+    // the body starts after the first empty line, as when parsing the item
+    let body_start = match lines.iter().position(|l| l.trim_end_matches('\r')
+                                                      .len() == 0) {
+        Some(i) => i + 1,
+        None    => lines.len(),
+    };
+
+    let steps = todo_item::parse_checklist(&lines[body_start..].join("\n"));
+    let step = match steps.into_iter().nth(n.wrapping_sub(1)) {
+        Some(step)  => step,
+        None        => {
+            let err_msg = format!("'{}' has no step {}", item.heading, n);
+            return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+        },
+    };
+    let mark = if step.done { " " } else { "x" };
+    lines[body_start + step.line].replace_range(step.mark..step.mark + 1,
+                                                mark);
+    try!(store.save(&item.filename, &lines.join("\n")));
+    Ok(step)
+}
+
+
+/// Fail if `item` has been changed or removed in `store` since it was read,
+/// so that changes made elsewhere are not overwritten.
+pub fn check_unchanged(store: &dyn Store, item: &TodoItem) -> TodoResult<()> {
+    let stamp = store.stamp(&item.filename);
+    match item.stamp {
+        Some(ref loaded) if stamp.as_ref() != Some(loaded) => {
+            let err_msg = format!("'{}' has changed since it was read, not \
+                                   overwriting it", item.filename);
+            Err(TodoError::new(TodoErrorKind::Other, err_msg))
+        },
+        _   => Ok(()),
+    }
+}
+
+
+fn attr_line_key(line: &str) -> Option<&str> {
+    line.splitn(2, ':').next().map(|k| k.trim())
+}
+
+
+fn replace_attrs(contents: &str, attrs: &[Attr]) -> TodoResult<String> {
+    let mut lines: Vec<String> = contents.lines()
+                                         .map(|l| l.to_string())
+                                         .collect();
+
+    if lines.len() == 0 || lines[0].trim().len() == 0 {
+        return Err(TodoError::new(TodoErrorKind::Parse,
+                                  "Heading not found".to_string()));
+    }
+
+    // header is the heading and the attrs up to the first empty line
+    let mut header_end = lines.iter()
+                              .position(|l| l.len() == 0)
+                              .unwrap_or(lines.len());
+
+    for attr in attrs {
+        let new_line = format!("{}: {}", attr.key, attr.value);
+        let existing = (1..header_end).find(|&i| {
+            attr_line_key(&lines[i]) == Some(&attr.key[..])
+        });
+
+        match existing {
+            Some(i) => { lines[i] = new_line; },
+            None    => {
+                lines.insert(header_end, new_line);
+                header_end += 1;
+            },
+        };
+    }
+
+    let mut new_contents = lines.join("\n");
+    if contents.ends_with('\n') {
+        new_contents.push('\n');
+    }
+    Ok(new_contents)
+}
+
+
+fn remove_attrs(contents: &str, keys: &[&str]) -> String {
+    let mut in_header = true;
+    let mut new_contents = String::new();
+    for (i, line) in contents.split_terminator('\n').enumerate() {
+        if line.len() == 0 {
+            in_header = false;
+        }
+        let key = attr_line_key(line).unwrap_or("");
+        if i > 0 && in_header && keys.contains(&key) {
+            continue;
+        }
+        new_contents.push_str(line);
+        new_contents.push('\n');
+    }
+    if !contents.ends_with('\n') {
+        new_contents.pop();
+    }
+    new_contents
+}
+
+
+fn replace_heading(contents: &str, heading: &str) -> String {
+    match contents.find('\n') {
+        Some(i) => format!("{}{}", heading, &contents[i..]),
+        None    => heading.to_string(),
+    }
+}
@@ -0,0 +1,383 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use time;
+
+use error::{TodoError, TodoErrorKind, TodoResult};
+use git;
+use opt::Opt;
+use store::Store;
+
+const JOURNAL_FILE: &'static str = ".journal";
+// number of operations --log shows
+const LOG_LEN: usize = 20;
+
+
+/// A change made by one action, possibly to several items. Create it before
+/// changing anything, `touch` every item before changing it and `finish` it
+/// when done: that records the change in the journal and, in git mode,
+/// commits it.
+pub struct Op {
+    files:      Vec<(String, Option<String>)>,
+    kind:       String,
+    summary:    String,
+}
+
+
+/// An operation as read back from the journal.
+struct Record {
+    files:      Vec<FileChange>,
+    kind:       String,
+    summary:    String,
+    time:       String,
+}
+
+
+struct FileChange {
+    after:      Option<String>,
+    before:     Option<String>,
+    key:        String,
+}
+
+
+enum Entry {
+    Op(Record),
+    Undo(usize),
+    Redo(usize),
+}
+
+
+impl Op {
+    pub fn new(kind: &str, summary: &str) -> Op {
+        Op {
+            files:      Vec::new(),
+            kind:       kind.to_string(),
+            summary:    summary.to_string(),
+        }
+    }
+
+
+    /// Remember the contents of item `key` before it is changed.
+    pub fn touch(&mut self, store: &dyn Store, key: &str) -> TodoResult<()> {
+        if self.files.iter().all(|&(ref k, _)| k != key) {
+            let before = try!(read(store, key));
+            self.files.push((key.to_string(), before));
+        }
+        Ok(())
+    }
+
+
+    /// Remember that item `key` was created, and did not exist before.
+    pub fn created(&mut self, key: &str) {
+        if self.files.iter().all(|&(ref k, _)| k != key) {
+            self.files.push((key.to_string(), None));
+        }
+    }
+
+
+    /// Record the change in the journal and commit it in git mode. Items
+    /// that did not change are left out, and nothing is recorded if none of
+    /// them did.
+    pub fn finish(self, opt: &Opt, store: &dyn Store) -> TodoResult<()> {
+        let mut files: Vec<FileChange> = Vec::new();
+        for (key, before) in self.files {
+            let after = try!(read(store, &key));
+            if after != before {
+                files.push(FileChange {
+                    after:      after,
+                    before:     before,
+                    key:        key,
+                });
+            }
+        }
+        if files.len() == 0 {
+            return Ok(());
+        }
+
+        let record = Record {
+            files:      files,
+            kind:       self.kind,
+            summary:    self.summary,
+            time:       try!(time::strftime("%Y-%m-%d %H:%M", &time::now())),
+        };
+        try!(append(store, &encode_record(&record)));
+        commit(opt, &record.kind, &record.summary, &record)
+    }
+}
+
+
+/// Read item `key`, or None if the store does not have it.
+fn read(store: &dyn Store, key: &str) -> TodoResult<Option<String>> {
+    if store.stamp(key).is_none() {
+        return Ok(None);
+    }
+    store.load(key).map(Some)
+}
+
+
+fn commit(opt: &Opt, kind: &str, summary: &str, record: &Record)
+          -> TodoResult<()> {
+    let paths: Vec<&str> = record.files.iter().map(|f| &f.key[..]).collect();
+    git::commit(opt, kind, summary, &paths)
+}
+
+
+/// Append `text` to the journal with a single write, so that entries of
+/// concurrent todo processes do not get mixed.
+fn append(store: &dyn Store, text: &str) -> TodoResult<()> {
+    store.append_meta(JOURNAL_FILE, text)
+}
+
+
+/// Read the journal. An entry that can not be parsed, like one cut short
+/// by a crash, is skipped with a warning.
+fn read_journal(store: &dyn Store) -> TodoResult<Vec<Entry>> {
+    let contents = match try!(store.load_meta(JOURNAL_FILE)) {
+        Some(contents)  => contents,
+        None            => return Ok(Vec::new()),
+    };
+
+    let mut entries = Vec::new();
+    let mut lines = contents.lines();
+    while let Some(line) = lines.next() {
+        let mut words = line.splitn(2, ' ');
+        let entry = match (words.next(), words.next()) {
+            (Some("op"), Some(rest))    => decode_record(rest, &mut lines),
+            (Some("undo"), Some(n))     => n.parse().ok().map(Entry::Undo),
+            (Some("redo"), Some(n))     => n.parse().ok().map(Entry::Redo),
+            _                           => None,
+        };
+        match entry {
+            Some(entry) => entries.push(entry),
+            None        => print_err!("Skipping broken journal entry '{}'",
+                                      line),
+        };
+    }
+    Ok(entries)
+}
+
+
+/// The operations in the journal, the ones in effect and the undone ones
+/// that can be redone. The lists are indexes to the operations, the last
+/// ones are the most recent.
+fn replay(entries: Vec<Entry>) -> (Vec<Record>, Vec<usize>, Vec<usize>) {
+    let mut records = Vec::new();
+    let mut done: Vec<usize> = Vec::new();
+    let mut undone: Vec<usize> = Vec::new();
+    for entry in entries {
+        match entry {
+            Entry::Op(record)   => {
+                done.push(records.len());
+                records.push(record);
+                // a new change makes the undone ones impossible to redo
+                undone.clear();
+            },
+            Entry::Undo(n)      => {
+                done.retain(|&i| i != n);
+                undone.push(n);
+            },
+            Entry::Redo(n)      => {
+                undone.retain(|&i| i != n);
+                done.push(n);
+            },
+        };
+    }
+    (records, done, undone)
+}
+
+
+/// Undo the last `count` operations. Without a journal, in git mode, the
+/// last commits made by todo are reverted instead.
+pub fn undo(opt: &Opt, store: &dyn Store, count: usize) -> TodoResult<()> {
+    let _lock = try!(store.lock());
+    let entries = try!(read_journal(store));
+    // changes committed by todo before there was a journal are undone in git
+    if entries.len() == 0 && opt.git {
+        for _ in 0..count {
+            try!(git::undo(opt));
+        }
+        return Ok(());
+    }
+    let (records, mut done, _) = replay(entries);
+    for _ in 0..count {
+        let n = match done.pop() {
+            Some(n) => n,
+            None    => {
+                return Err(TodoError::new(TodoErrorKind::Other,
+                                          "Nothing to undo".to_string()));
+            },
+        };
+        let record = &records[n];
+        try!(apply(store, record, true));
+        try!(append(store, &format!("undo {}\n", n)));
+        println!("Undid: {}", record.summary);
+        try!(commit(opt, "undo", &format!("Undo: {}", record.summary),
+                    record));
+    }
+    Ok(())
+}
+
+
+/// Redo the last undone operation.
+pub fn redo(opt: &Opt, store: &dyn Store) -> TodoResult<()> {
+    let _lock = try!(store.lock());
+    let (records, _, mut undone) = replay(try!(read_journal(store)));
+    let n = match undone.pop() {
+        Some(n) => n,
+        None    => {
+            return Err(TodoError::new(TodoErrorKind::Other,
+                                      "Nothing to redo".to_string()));
+        },
+    };
+    let record = &records[n];
+    try!(apply(store, record, false));
+    try!(append(store, &format!("redo {}\n", n)));
+    println!("Redid: {}", record.summary);
+    commit(opt, "redo", &format!("Redo: {}", record.summary), record)
+}
+
+
+/// Put the items of `record` back as they were before it, or as they were
+/// after it when redoing. Nothing is changed if any item has been changed
+/// since by something else than todo.
+fn apply(store: &dyn Store, record: &Record, undo: bool) -> TodoResult<()> {
+    for file in &record.files {
+        let expected = if undo { &file.after } else { &file.before };
+        if try!(read(store, &file.key)) != *expected {
+            let err_msg = format!("'{}' has changed since '{}', not \
+                                   touching it", file.key, record.summary);
+            return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+        }
+    }
+
+    for file in &record.files {
+        let target = if undo { &file.before } else { &file.after };
+        match *target {
+            Some(ref contents)  => try!(store.save(&file.key, contents)),
+            None                => try!(store.delete(&file.key)),
+        };
+    }
+    Ok(())
+}
+
+
+/// Print the most recent operations, newest first.
+pub fn log(store: &dyn Store) -> TodoResult<()> {
+    let (records, _, undone) = replay(try!(read_journal(store)));
+    if records.len() == 0 {
+        println!("No changes recorded.");
+        return Ok(());
+    }
+
+    for (n, record) in records.iter().enumerate().rev().take(LOG_LEN) {
+        let state = if undone.contains(&n) { " (undone)" } else { "" };
+        println!("{:4} {} {}: {}{}", n, record.time, record.kind,
+                 record.summary, state);
+    }
+    Ok(())
+}
+
+
+/// Write a record as:
+///
+///     op <time>\t<kind>\t<summary>
+///     file <key>
+///     before <contents>
+///     after <contents>
+///     end
+///
+/// with a file, before and after line for every item. Contents are quoted
+/// and escaped, or "none" for an item that did not exist.
+fn encode_record(record: &Record) -> String {
+    let mut text = format!("op {}\t{}\t{}\n", record.time, record.kind,
+                           record.summary.replace('\n', " "));
+    for file in &record.files {
+        text.push_str(&format!("file {}\n", file.key));
+        text.push_str(&format!("before {}\n", encode_contents(&file.before)));
+        text.push_str(&format!("after {}\n", encode_contents(&file.after)));
+    }
+    text.push_str("end\n");
+    text
+}
+
+
+fn decode_record<'a, I>(header: &str, lines: &mut I) -> Option<Entry>
+        where I: Iterator<Item=&'a str> {
+    let mut fields = header.splitn(3, '\t');
+    let mut record = Record {
+        files:      Vec::new(),
+        time:       try_opt!(fields.next()).to_string(),
+        kind:       try_opt!(fields.next()).to_string(),
+        summary:    try_opt!(fields.next()).to_string(),
+    };
+
+    loop {
+        let line = try_opt!(lines.next());
+        if line == "end" {
+            return Some(Entry::Op(record));
+        }
+        if !line.starts_with("file ") {
+            return None;
+        }
+        let key = line["file ".len()..].to_string();
+        let before = try_opt!(lines.next().and_then(|l| decode_line(l, "before ")));
+        let after = try_opt!(lines.next().and_then(|l| decode_line(l, "after ")));
+        record.files.push(FileChange {
+            after:      after,
+            before:     before,
+            key:        key,
+        });
+    }
+}
+
+
+fn encode_contents(contents: &Option<String>) -> String {
+    let contents = match *contents {
+        Some(ref contents)  => contents,
+        None                => { return "none".to_string(); },
+    };
+    let mut text = String::from("\"");
+    for c in contents.chars() {
+        match c {
+            '\\'    => text.push_str("\\\\"),
+            '"'     => text.push_str("\\\""),
+            '\n'    => text.push_str("\\n"),
+            '\r'    => text.push_str("\\r"),
+            '\t'    => text.push_str("\\t"),
+            c       => text.push(c),
+        };
+    }
+    text.push('"');
+    text
+}
+
+
+/// Decode a before or after line, starting with `prefix`.
+fn decode_line(line: &str, prefix: &str) -> Option<Option<String>> {
+    if !line.starts_with(prefix) {
+        return None;
+    }
+    let value = &line[prefix.len()..];
+    if value == "none" {
+        return Some(None);
+    }
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return None;
+    }
+
+    let mut contents = String::new();
+    let mut chars = value[1..value.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            contents.push(c);
+            continue;
+        }
+        contents.push(match try_opt!(chars.next()) {
+            'n'     => '\n',
+            'r'     => '\r',
+            't'     => '\t',
+            c       => c,
+        });
+    }
+    Some(Some(contents))
+}
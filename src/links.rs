@@ -0,0 +1,87 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::rc::Rc;
+
+use error::TodoResult;
+use todo_item::TodoItem;
+use todo_items;
+
+
+/// Get the links of `item`: the "[[reference]]" ones in the body and the
+/// ones in the `related` attrs, in order and without duplicates.
+pub fn references(item: &TodoItem) -> Vec<String> {
+    let mut refs: Vec<String> = Vec::new();
+    let mut rest = item.body.as_ref().map_or("", |b| &b[..]);
+    while let Some(start) = rest.find("[[") {
+        rest = &rest[start + 2..];
+        match rest.find("]]") {
+            // a link does not go over lines
+            Some(end) if !rest[..end].contains('\n') => {
+                refs.push(rest[..end].trim().to_string());
+                rest = &rest[end + 2..];
+            },
+            _   => {},
+        };
+    }
+    for attr in item.attrs.iter().filter(|a| {
+        a.key == todo_items::RELATED_ATTR
+    }) {
+        refs.extend(attr.value.split(',').map(|r| r.trim().to_string()));
+    }
+
+    let mut unique: Vec<String> = Vec::new();
+    for reference in refs {
+        if reference.len() > 0 && !unique.contains(&reference) {
+            unique.push(reference);
+        }
+    }
+    unique
+}
+
+
+/// Find the item `reference` links to, by its stable ID, file name or
+/// heading. Headings are matched without minding the case.
+pub fn resolve(items: &Vec<Rc<TodoItem>>, reference: &str)
+               -> Option<Rc<TodoItem>> {
+    items.iter()
+         .find(|i| todo_items::refers_to(reference, i))
+         .or_else(|| {
+             let lower = reference.to_lowercase();
+             items.iter().find(|i| i.heading.to_lowercase() == lower)
+         })
+         .cloned()
+}
+
+
+/// Get the items that link to `item`.
+pub fn get_backlinks(items: &Vec<Rc<TodoItem>>, item: &TodoItem)
+                     -> Vec<Rc<TodoItem>> {
+    items.iter().filter(|other| {
+        other.id != item.id && references(other).iter().any(|r| {
+            resolve(items, r).map_or(false, |i| i.id == item.id)
+        })
+    }).cloned().collect()
+}
+
+
+/// Warn about the links to items that do not exist, and print how many
+/// were found.
+pub fn check(items: &Vec<Rc<TodoItem>>) -> TodoResult<()> {
+    let mut broken = 0;
+    for item in items {
+        for reference in references(item) {
+            if resolve(items, &reference).is_none() {
+                print_err!("Warning: '{}' ({}) links to '{}', which does not \
+                            exist", item.heading, item.filename, reference);
+                broken += 1;
+            }
+        }
+    }
+    if broken == 0 {
+        println!("No problems found.");
+    } else {
+        println!("{} broken links.", broken);
+    }
+    Ok(())
+}
@@ -0,0 +1,242 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use time::Tm;
+
+use util;
+
+const MONTHS: [&'static str; 12] = ["jan", "feb", "mar", "apr", "may", "jun",
+                                    "jul", "aug", "sep", "oct", "nov", "dec"];
+
+
+/// An email message, split to its headers and body. Only as much of MIME is
+/// understood as is needed to get the text of a message.
+pub struct Mail {
+    body:       String,
+    headers:    Vec<(String, String)>,
+}
+
+
+impl Mail {
+    pub fn parse(text: &str) -> Mail {
+        let text = text.replace("\r\n", "\n");
+        let (head, body) = match text.find("\n\n") {
+            Some(i) => (&text[..i], &text[i + 2..]),
+            None    => (&text[..], ""),
+        };
+
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in head.lines() {
+            // skip the mbox "From " line
+            if line.starts_with("From ") && headers.len() == 0 {
+                continue;
+            }
+            if line.starts_with(' ') || line.starts_with('\t') {
+                // a folded header goes on
+                if let Some(&mut (_, ref mut value)) = headers.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+                continue;
+            }
+            if let Some(i) = line.find(':') {
+                headers.push((line[..i].trim().to_lowercase(),
+                              line[i + 1..].trim().to_string()));
+            }
+        }
+
+        Mail {
+            body:       body.to_string(),
+            headers:    headers,
+        }
+    }
+
+
+    /// Get the first header `name`, with any encoded words decoded.
+    pub fn header(&self, name: &str) -> Option<String> {
+        let name = name.to_lowercase();
+        self.headers.iter().find(|&&(ref n, _)| *n == name)
+                           .map(|&(_, ref value)| decode_words(value))
+    }
+
+
+    /// Get the date of the Date header, as written by the sender.
+    pub fn date(&self) -> Option<Tm> {
+        let value = try_opt!(self.header("date"));
+        // e.g. "Tue, 1 Mar 2016 10:00:00 +0200", the weekday is optional
+        let value = match value.find(',') {
+            Some(i) => &value[i + 1..],
+            None    => &value[..],
+        };
+        let mut words = value.split_whitespace();
+        let day = try_opt!(try_opt!(words.next()).parse::<u32>().ok());
+        let month = try_opt!(words.next()).to_lowercase();
+        let month = try_opt!(MONTHS.iter().position(|&m| month.starts_with(m)));
+        let year = try_opt!(try_opt!(words.next()).parse::<u32>().ok());
+        let date = format!("{:04}-{:02}-{:02}", year, month + 1, day);
+        util::str_to_date(&date).ok()
+    }
+
+
+    /// Get the text of the message: the body, or the first text/plain part
+    /// of a multipart message.
+    pub fn text(&self) -> String {
+        let content_type = self.header("content-type")
+                               .unwrap_or("text/plain".to_string());
+        if !content_type.to_lowercase().starts_with("multipart/") {
+            let encoding = self.header("content-transfer-encoding");
+            return decode_body(&self.body, encoding.as_ref().map(|e| &e[..]));
+        }
+
+        let boundary = match param(&content_type, "boundary") {
+            Some(boundary)  => format!("--{}", boundary),
+            None            => { return self.body.clone(); },
+        };
+        for part in self.body.split(&boundary[..]).skip(1) {
+            // the closing boundary
+            if part.starts_with("--") {
+                break;
+            }
+            let part = Mail::parse(part.trim_start_matches('\n'));
+            let part_type = part.header("content-type")
+                                .unwrap_or("text/plain".to_string());
+            if part_type.to_lowercase().starts_with("text/plain") {
+                return part.text();
+            }
+        }
+        String::new()
+    }
+}
+
+
+/// Get parameter `name` of a header value like 'multipart/mixed;
+/// boundary="abc"'.
+fn param(value: &str, name: &str) -> Option<String> {
+    for p in value.split(';').skip(1) {
+        let mut kv = p.splitn(2, '=');
+        if try_opt!(kv.next()).trim().to_lowercase() == name {
+            return kv.next().map(|v| v.trim().trim_matches('"').to_string());
+        }
+    }
+    None
+}
+
+
+fn decode_body(body: &str, encoding: Option<&str>) -> String {
+    let bytes = match encoding.map(|e| e.to_lowercase()) {
+        Some(ref e) if e == "base64"            => decode_base64(body),
+        Some(ref e) if e == "quoted-printable"  => decode_qp(body, false),
+        _                                       => body.as_bytes().to_vec(),
+    };
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+
+/// Decode the encoded words of a header, like "=?UTF-8?Q?Caf=C3=A9?=".
+/// Text in charsets other than UTF-8 and Latin-1 is decoded as if it was
+/// UTF-8.
+fn decode_words(value: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = value;
+    // whitespace between two encoded words is dropped
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let word = decode_word(&rest[start..]);
+        let (text, len) = match word {
+            Some(w) => w,
+            None    => {
+                decoded.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+                after_word = false;
+                continue;
+            },
+        };
+        let between = &rest[..start];
+        if !(after_word && between.trim().len() == 0) {
+            decoded.push_str(between);
+        }
+        decoded.push_str(&text);
+        rest = &rest[start + len..];
+        after_word = true;
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+
+/// Decode the encoded word at the start of `s`, returning the text and the
+/// length of the word.
+fn decode_word(s: &str) -> Option<(String, usize)> {
+    let mut parts = s[2..].splitn(3, '?');
+    let charset = try_opt!(parts.next()).to_lowercase();
+    let encoding = try_opt!(parts.next()).to_lowercase();
+    let rest = try_opt!(parts.next());
+    let end = try_opt!(rest.find("?="));
+    let text = &rest[..end];
+    let len = 2 + charset.len() + 1 + encoding.len() + 1 + end + 2;
+
+    let bytes = match &encoding[..] {
+        "b" => decode_base64(text),
+        "q" => decode_qp(text, true),
+        _   => { return None; },
+    };
+    let text = if charset == "iso-8859-1" || charset == "latin1" {
+        bytes.iter().map(|&b| b as char).collect()
+    } else {
+        String::from_utf8_lossy(&bytes).into_owned()
+    };
+    Some((text, len))
+}
+
+
+fn decode_base64(text: &str) -> Vec<u8> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+'        => Some(62),
+        b'/'        => Some(63),
+        _           => None,
+    };
+
+    let mut bytes = Vec::new();
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for v in text.bytes().filter_map(value) {
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    bytes
+}
+
+
+/// Decode quoted-printable text. In headers '_' stands for a space.
+fn decode_qp(text: &str, header: bool) -> Vec<u8> {
+    let hex = |c: u8| (c as char).to_digit(16);
+    let text = text.as_bytes();
+    let mut bytes = Vec::new();
+    let mut i = 0;
+    while i < text.len() {
+        match text[i] {
+            b'=' if i + 1 < text.len() && text[i + 1] == b'\n' => { i += 1; },
+            b'=' if i + 2 < text.len() => {
+                match (hex(text[i + 1]), hex(text[i + 2])) {
+                    (Some(h), Some(l))  => {
+                        bytes.push((h * 16 + l) as u8);
+                        i += 2;
+                    },
+                    _                   => bytes.push(b'='),
+                };
+            },
+            b'_' if header  => bytes.push(b' '),
+            c               => bytes.push(c),
+        };
+        i += 1;
+    }
+    bytes
+}
@@ -1,34 +1,67 @@
 // Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
 // Licensed under the 2-clause BSD license, see LICENSE for details.
 
+extern crate crossterm;
 extern crate getopts;
+extern crate libc;
+extern crate regex;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+extern crate terminal_size;
 extern crate time;
+extern crate unicode_width;
 
 // keep macros up here to be able to use them in submodules
 #[macro_use]
 mod util;
 mod action;
 mod attr;
+mod bulk;
+mod cache;
+mod calendar;
+mod capture;
+mod clock;
+mod color;
+mod config;
+mod depends;
 mod error;
+mod git;
+#[cfg(feature = "sqlite")]
+mod index;
+mod item_file;
+mod journal;
+mod links;
+mod mail;
 mod opt;
 mod optutil;
+mod priority;
+mod search;
 mod status;
+mod store;
+mod template;
 mod todo_item;
 mod todo_items;
+mod tui;
+mod watch;
 
 use getopts::Options;
 use std::env;
+use std::rc::Rc;
 
 use action::Action;
+use error::{TodoError, TodoErrorKind, TodoResult};
 use opt::Opt;
-use todo_items::get_todo_items;
+use store::DirStore;
+use todo_item::TodoItem;
+use todo_items::{get_todo_headers, get_todo_items};
 
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let program = args[0].clone();
     let opts_in: Options = optutil::get_options();
-    let opts: Opt = match optutil::parse_options(&args, &opts_in) {
+    let store = DirStore::new(opt::todo_dir().as_path());
+    let mut opts: Opt = match optutil::parse_options(&args, &opts_in, &store) {
         Ok(opt) => opt,
         Err(e)  => {
             print_err!("Error parsing options: {}", e);
@@ -55,21 +88,190 @@ fn main() {
     }
 
     // "proper" actions
-    match get_todo_items(opts.todo_dir.as_path()) {
+    match load_items(&opts, &store, &action) {
+        Ok(items) if opts.watch => {
+            if let Err(e) = watch::watch(&opts, &store, &action, items) {
+                print_err!("Error watching items: {}", e);
+            }
+        },
         Ok(mut items)   => {
+            if let Err(e) = select(&mut opts, &items, &action) {
+                print_err!("Error selecting item: {}", e);
+                return;
+            }
+            let many = match action {
+                Action::BulkEdit | Action::Delete | Action::Done |
+                Action::Postpone | Action::Tag  => true,
+                _               => false,
+            };
+            if !many && opts.item_ids.len() > 1 {
+                print_err!("Error: '{}' selects {} items, select one",
+                           opts.selector.as_ref().or(opts.search.as_ref())
+                                        .unwrap(),
+                           opts.item_ids.len());
+                return;
+            }
             match action {
-                Action::Agenda  => { action::agenda(&opts, &items); },
-                Action::Delete  => { action::delete_item(&mut items, opts.item_id); },
+                Action::Add     => {
+                    let heading = opts.heading.clone().unwrap_or(String::new());
+                    match action::add_item(&opts, &store, &items, &heading) {
+                        Err(e)  => { print_err!("Error adding item: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Agenda  => {
+                    match action::agenda(&opts, &items) {
+                        Err(e)  => { print_err!("Error showing agenda: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Blocked => {
+                    match depends::blocked(&opts, &items) {
+                        Err(e)  => { print_err!("Error listing blocked items: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::BulkEdit    => {
+                    match bulk::bulk_edit(&opts, &store, &items) {
+                        Err(e)  => { print_err!("Error bulk editing items: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Capture => {
+                    match capture::capture(&opts, &store) {
+                        Err(e)  => { print_err!("Error capturing item: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::CaptureMail => {
+                    match capture::capture_mail(&opts, &store) {
+                        Err(e)  => { print_err!("Error capturing mail: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Check   => {
+                    if let Err(e) = links::check(&items) {
+                        print_err!("Error checking items: {}", e);
+                    }
+                },
+                Action::CheckStep   => {
+                    match action::check_step(&opts, &store, &items) {
+                        Err(e)  => { print_err!("Error checking step: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Delete  => {
+                    match action::delete_items(&opts, &store, &mut items) {
+                        Err(e)  => { print_err!("Error deleting items: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Done    => {
+                    match action::mark_done(&opts, &store, &items) {
+                        Err(e)  => { print_err!("Error marking items done: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
                 Action::Dump    => { action::dump(&items); },
                 Action::Edit    => {
-                    match action::edit_item(&items, opts.item_id, &opts.editor) {
+                    match action::edit_item(&opts, &store, &items,
+                                            opts.item_id) {
                         Err(e)  => { print_err!("Error editing item: {}", e ) },
                         Ok(())  => {},
                     }
                 },
+                Action::Graph   => {
+                    match depends::graph(&items) {
+                        Err(e)  => { print_err!("Error drawing the graph: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::History => {
+                    match git::history(&opts, &items, opts.item_id) {
+                        Err(e)  => { print_err!("Error showing history: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Log     => {
+                    match journal::log(&store) {
+                        Err(e)  => { print_err!("Error showing log: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Month   => {
+                    match action::month(&opts, &items) {
+                        Err(e)  => { print_err!("Error showing month: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::New     => {
+                    match action::new_item(&opts, &store,
+                                           opts.template.as_ref()
+                                               .map(|t| &t[..])) {
+                        Err(e)  => { print_err!("Error adding item: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Postpone    => {
+                    match action::postpone(&opts, &store, &items) {
+                        Err(e)  => { print_err!("Error postponing item: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Ready   => {
+                    match depends::ready(&opts, &items) {
+                        Err(e)  => { print_err!("Error listing ready items: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Redo    => {
+                    match journal::redo(&opts, &store) {
+                        Err(e)  => { print_err!("Error redoing change: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Rollover    => {
+                    match action::rollover(&opts, &store, &items) {
+                        Err(e)  => { print_err!("Error rolling over items: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Search  => {
+                    match search::search(&opts, &items) {
+                        Err(e)  => { print_err!("Error searching items: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
                 Action::Show    => { action::show_item(&items, opts.item_id); },
-                Action::Today   => { action::print_today(&items); },
-                Action::TodayOnly   => { action::print_today_only(&items); },
+                Action::Tag     => {
+                    let tag = opts.tag.as_ref().map_or("", |t| &t[..]);
+                    match action::tag_items(&opts, &store, &items, tag) {
+                        Err(e)  => { print_err!("Error tagging items: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Today   => { action::print_today(&opts, &items); },
+                Action::TodayOnly   => {
+                    action::print_today_only(&opts, &items);
+                },
+                Action::Tui     => {
+                    match tui::run(&opts, &store, items) {
+                        Err(e)  => { print_err!("Error in interactive view: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Undo    => {
+                    match journal::undo(&opts, &store, opts.undo_count) {
+                        Err(e)  => { print_err!("Error undoing change: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
+                Action::Week    => {
+                    match action::week(&opts, &items) {
+                        Err(e)  => { print_err!("Error showing week: {}", e) },
+                        Ok(())  => {},
+                    }
+                },
                 _               => {},
             }
         },
@@ -78,6 +280,36 @@ fn main() {
 }
 
 
+/// Look up the items selected for the action, with the selector or the
+/// search pattern or both.
+fn select(opts: &mut Opt, items: &Vec<Rc<TodoItem>>, action: &Action)
+          -> TodoResult<()> {
+    let mut ids = match opts.selector {
+        Some(ref selector)  => Some(try!(todo_items::select_items(items,
+                                                                  selector))),
+        None                => None,
+    };
+    if opts.search.is_some() && *action != Action::Search {
+        let found = try!(search::select_items(opts, items));
+        ids = Some(match ids {
+            Some(ids)   => ids.into_iter().filter(|id| found.contains(id))
+                                          .collect(),
+            None        => found,
+        });
+    }
+
+    if let Some(ids) = ids {
+        if ids.len() == 0 {
+            return Err(TodoError::new(TodoErrorKind::Other,
+                                      "No item matches".to_string()));
+        }
+        opts.item_id = ids[0];
+        opts.item_ids = ids;
+    }
+    Ok(())
+}
+
+
 /// Check that only one action has been called
 fn check_actions(actions: &Vec<Action>) -> Option<Action> {
     if actions.len() == 0 {
@@ -90,3 +322,36 @@ fn check_actions(actions: &Vec<Action>) -> Option<Action> {
         return actions.last().cloned();
     }
 }
+
+
+/// Load the items, through the SQLite index if it is enabled
+#[cfg(feature = "sqlite")]
+fn load_items(opts: &Opt, store: &DirStore, action: &Action)
+              -> TodoResult<Vec<Rc<TodoItem>>> {
+    if opts.index {
+        index::get_todo_items(opts, store, action)
+    } else {
+        load_files(opts, store, action)
+    }
+}
+
+
+#[cfg(not(feature = "sqlite"))]
+fn load_items(opts: &Opt, store: &DirStore, action: &Action)
+              -> TodoResult<Vec<Rc<TodoItem>>> {
+    load_files(opts, store, action)
+}
+
+
+/// Read the item files, with the bodies only for the actions that use them
+fn load_files(opts: &Opt, store: &DirStore, action: &Action)
+              -> TodoResult<Vec<Rc<TodoItem>>> {
+    match *action {
+        // a search looks in the bodies too
+        _ if opts.search.is_some()  => get_todo_items(store),
+        Action::Check | Action::Dump | Action::Show | Action::Tui => {
+            get_todo_items(store)
+        },
+        _   => get_todo_headers(store),
+    }
+}
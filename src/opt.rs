@@ -5,15 +5,54 @@ use std::env;
 use std::path::PathBuf;
 
 use action::Action;
+use clock::Clock;
+use color::{ColorMode, Theme};
 
 #[derive(Debug)]
 pub struct Opt {
     pub actions:    Vec<Action>,
     pub agenda_days:i64,
+    pub agenda_from:Option<String>,
+    pub agenda_past:Option<i64>,
+    pub agenda_to:  Option<String>,
+    pub clock:      Clock,
+    pub color:      ColorMode,
     pub debug:      bool,
+    pub dry_run:    bool,
     pub editor:     Option<String>,
+    pub force:      bool,
+    pub git:        bool,
+    pub heading:    Option<String>,
+    pub ignore_case:bool,
+    pub index:      bool,
     pub item_id:    i32,
+    pub item_ids:   Vec<i32>,
+    pub parent:     Option<String>,
+    pub postpone_to:Option<String>,
+    pub regex:      bool,
+    pub search:     Option<String>,
+    pub selector:   Option<String>,
+    pub step:       usize,
+    pub tag:        Option<String>,
+    pub template:   Option<String>,
+    pub theme:      Theme,
     pub todo_dir:   PathBuf,
+    pub watch:      bool,
+    pub undo_count: usize,
+    pub with_blocked:bool,
+    pub workday:    bool,
+    pub yes:        bool,
+}
+
+
+/// Get the todo dir, .todo in the home dir.
+pub fn todo_dir() -> PathBuf {
+    let mut todo_dir = match env::home_dir() {
+        Some(path) => path,
+        None => { panic!("Could not get home dir"); },
+    };
+    todo_dir.push(".todo/");
+    todo_dir
 }
 
 
@@ -29,20 +68,39 @@ impl Opt {
                                         .or(env::var("EDITOR"))
                                         .ok();
 
-        // get todo dir
-        let mut todo_dir = match env::home_dir() {
-            Some(path) => path,
-            None => { panic!("Could not get home dir"); },
-        };
-        todo_dir.push(".todo/");
-
         Opt {
             actions:    Vec::new(),
             agenda_days:8,
+            agenda_from:None,
+            agenda_past:None,
+            agenda_to:  None,
+            clock:      Clock::system(),
+            color:      ColorMode::Auto,
             debug:      false,
+            dry_run:    false,
             editor:     editor,
+            force:      false,
+            git:        false,
+            heading:    None,
+            ignore_case:false,
+            index:      false,
             item_id:    0,
-            todo_dir:   todo_dir,
+            item_ids:   Vec::new(),
+            parent:     None,
+            postpone_to:None,
+            regex:      false,
+            search:     None,
+            selector:   None,
+            step:       0,
+            tag:        None,
+            template:   None,
+            theme:      Theme::by_name("default").unwrap(),
+            todo_dir:   todo_dir(),
+            watch:      false,
+            undo_count: 1,
+            with_blocked:false,
+            workday:    false,
+            yes:        false,
         }
     }
 }
@@ -1,35 +1,87 @@
 // Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
 // Licensed under the 2-clause BSD license, see LICENSE for details.
 
+use std::env;
 use std::io::{Error, ErrorKind};
 
 use getopts::Options;
 
 use action::Action;
+use clock::Clock;
+use color;
+use config;
 use opt::Opt;
+use store::Store;
+use util;
 
 // TODO: rephrase option messages
 pub fn get_options() -> Options {
     let mut opts = Options::new();
     opts.optflag("a", "agenda", "show agenda");
+    opts.optopt("", "add", "add an item with HEADING, see --parent",
+                "HEADING");
     opts.optopt("A", "agenda-days", "set agenda days", "NUM");
+    opts.optflag("", "blocked", "list the items waiting for other items");
+    opts.optopt("", "bulk-edit", "edit the items matching FILTER as one \
+                document", "FILTER");
+    opts.optflag("", "capture", "add an item from the text in stdin");
+    opts.optflag("", "capture-mail", "add an item from the email in stdin");
+    opts.optflag("", "check", "warn about links to items that do not exist");
+    opts.optopt("", "check-step", "check or uncheck step N of the checklist \
+                of ITEM, as '--check-step ITEM N'", "ITEM");
+    opts.optopt("", "color", "use colors: auto, always or never", "WHEN");
+    opts.optopt("", "date", "use DATE as today's date", "YYYY-MM-DD");
     opts.optflag("D", "debug", "set debug mode");
+    opts.optflagopt("", "done", "mark ITEM done", "ITEM");
     opts.optflag("d", "dump", "show raw todo items");
-    opts.optflag("e", "edit", "edit item");
+    opts.optflagopt("e", "edit", "edit ITEM", "ITEM");
+    opts.optflag("f", "force", "mark items done even with open subtasks");
+    opts.optopt("", "from", "show agenda starting from DATE", "DATE|+N");
+    opts.optflag("", "graph", "print the dependencies as a Graphviz graph");
     opts.optflag("h", "help", "print this help");
-    opts.optopt("i", "id", "select item by ID", "ID");
-    opts.optflag("s", "show", "show item identified by -i");
+    opts.optopt("", "history", "show the git history of ITEM", "ITEM");
+    opts.optflag("I", "ignore-case", "search without minding the case");
+    opts.optopt("i", "id", "select items by ID, ID range, file name, stable \
+                ID prefix, heading or attr", "ITEM,...");
+    opts.optflag("", "log", "list the recent changes made by todo");
+    opts.optflag("m", "month", "show this month as a calendar grid");
+    opts.optflagopt("", "new", "write a new item from TEMPLATE in the editor",
+                    "TEMPLATE");
+    opts.optflag("n", "dry-run", "only show what would be changed");
+    opts.optopt("", "parent", "add the item as a subtask of ITEM", "ITEM");
+    opts.optopt("", "past", "show agenda for the past NUM days", "NUM");
+    opts.optopt("p", "postpone", "postpone ITEM, see --to", "ITEM");
+    opts.optflag("", "ready", "list the items not waiting for anything");
+    opts.optflag("", "redo", "redo the last undone change");
+    opts.optflag("", "regex", "search with a regular expression");
+    opts.optflag("R", "rollover", "move past undone items to today");
+    opts.optflagopt("s", "show", "show ITEM", "ITEM");
+    opts.optopt("", "search", "find items by text, or select the items for \
+                an action", "PATTERN");
     opts.optflag("t", "today", "print today's and past undone items");
     opts.optflag("T", "today-only", "print only today's items");
+    opts.optopt("", "to", "set agenda end date or postpone date",
+                "DATE|+N");
+    opts.optopt("", "tag", "add TAG to the items selected with -i", "TAG");
+    opts.optflag("", "tui", "start the interactive full-screen view");
+    opts.optflagopt("", "undo", "undo the last N changes made by todo", "N");
     opts.optflag("v", "version", "show version");
-    opts.optflag("X", "delete", "delete item");
+    opts.optflag("", "watch", "show the view again when items change");
+    opts.optflag("w", "week", "show this week as a calendar grid");
+    opts.optflag("", "with-blocked", "show blocked items for today too");
+    opts.optflag("W", "workday", "postpone weekend dates to next Monday");
+    opts.optflagopt("X", "delete", "delete ITEM", "ITEM");
+    opts.optflag("y", "yes", "change many items without asking");
     opts
 }
 
 
-pub fn parse_options(args: &Vec<String>, opts_in: &Options)
+/// Parse the command line `args` over the settings in the config file in
+/// `store`.
+pub fn parse_options(args: &Vec<String>, opts_in: &Options, store: &dyn Store)
                      -> Result<Opt, Error> {
     let mut opts: Opt = Opt::new();
+    try!(config::read_config(&mut opts, store));
 
     let matches = match opts_in.parse(&args[1..]) {
         Ok(m)   => { m }
@@ -40,6 +92,12 @@ pub fn parse_options(args: &Vec<String>, opts_in: &Options)
         opts.actions.push(Action::Agenda);
         match matches.opt_str("A") {
             Some(id)  => match id.parse::<i64>() {
+                Ok(i) if i < 1 => {
+                    let err_msg =
+                        format!("Invalid '--agenda-days' argument '{}': \
+                                must be at least 1, see '--past'", id);
+                    return Err(Error::new(ErrorKind::Other, err_msg));
+                },
                 Ok(i)    => { opts.agenda_days = i; },
                 Err(err) => {
                     let err_msg =
@@ -52,28 +110,158 @@ pub fn parse_options(args: &Vec<String>, opts_in: &Options)
         };
     };
     if matches.opt_present("a") { opts.actions.push(Action::Agenda); }
+    if let Some(heading) = matches.opt_str("add") {
+        opts.actions.push(Action::Add);
+        opts.heading = Some(heading);
+    }
+    if matches.opt_present("blocked") { opts.actions.push(Action::Blocked); }
+    if matches.opt_present("bulk-edit") {
+        opts.actions.push(Action::BulkEdit);
+    }
+    if matches.opt_present("capture") { opts.actions.push(Action::Capture); }
+    if matches.opt_present("capture-mail") {
+        opts.actions.push(Action::CaptureMail);
+    }
+    if let Some(mode) = matches.opt_str("color") {
+        match color::parse_color_mode(&mode) {
+            Some(m) => { opts.color = m; },
+            None    => {
+                let err_msg = format!("Invalid '--color' argument '{}'", mode);
+                return Err(Error::new(ErrorKind::Other, err_msg));
+            },
+        };
+    }
+    opts.color = color::resolve_color_mode(opts.color);
+    // --date overrides TODO_NOW
+    let date = matches.opt_str("date").or(env::var("TODO_NOW").ok());
+    if let Some(date) = date {
+        match util::str_to_date(&date) {
+            Ok(d)   => { opts.clock = Clock::fixed(&d); },
+            Err(err) => {
+                let err_msg = format!("Invalid date '{}': {}", date, err);
+                return Err(Error::new(ErrorKind::Other, err_msg));
+            },
+        };
+    }
     if matches.opt_present("D") { opts.debug = true; }
+    if matches.opt_present("check") { opts.actions.push(Action::Check); }
+    if matches.opt_present("check-step") {
+        opts.actions.push(Action::CheckStep);
+        let step = matches.free.first().map_or("", |s| &s[..]);
+        match step.parse::<usize>() {
+            Ok(n) if n >= 1 => { opts.step = n; },
+            _   => {
+                let err_msg = format!("Invalid '--check-step' step '{}': must \
+                                       be a number of at least 1", step);
+                return Err(Error::new(ErrorKind::Other, err_msg));
+            },
+        };
+    }
     if matches.opt_present("d") { opts.actions.push(Action::Dump); }
+    if matches.opt_present("done") { opts.actions.push(Action::Done); }
     if matches.opt_present("e") { opts.actions.push(Action::Edit); }
+    if matches.opt_present("from") {
+        opts.actions.push(Action::Agenda);
+        opts.agenda_from = matches.opt_str("from");
+    }
+    if matches.opt_present("f") { opts.force = true; }
+    if matches.opt_present("graph") { opts.actions.push(Action::Graph); }
     if matches.opt_present("h") { opts.actions.push(Action::Help); }
-    if matches.opt_present("i") {
-        match matches.opt_str("i") {
-            Some(id)  => match id.parse::<i32>() {
-                Ok(i)    => { opts.item_id = i; },
-                Err(err) => {
+    if matches.opt_present("history") {
+        opts.actions.push(Action::History);
+    }
+    if matches.opt_present("log") { opts.actions.push(Action::Log); }
+    if matches.opt_present("m") { opts.actions.push(Action::Month); }
+    if matches.opt_present("n") { opts.dry_run = true; }
+    if matches.opt_present("new") {
+        opts.actions.push(Action::New);
+        // accept both '--new=TEMPLATE' and '--new TEMPLATE'
+        opts.template = matches.opt_str("new")
+                               .or(matches.free.first().cloned());
+    }
+    if matches.opt_present("past") {
+        opts.actions.push(Action::Agenda);
+        match matches.opt_str("past") {
+            Some(days)  => match days.parse::<i64>() {
+                Ok(i) if i >= 1 => { opts.agenda_past = Some(i); },
+                _   => {
                     let err_msg =
-                        format!("Invalid item ID '{}': {}", id, err);
+                        format!("Invalid '--past' argument '{}': must be a \
+                                number of at least 1", days);
                     return Err(Error::new(ErrorKind::Other, err_msg));
                 },
             },
             None  => {},
         };
     }
+    if matches.opt_present("p") { opts.actions.push(Action::Postpone); }
+    opts.parent = matches.opt_str("parent");
+    if matches.opt_present("ready") { opts.actions.push(Action::Ready); }
+    if matches.opt_present("redo") { opts.actions.push(Action::Redo); }
+    if matches.opt_present("R") { opts.actions.push(Action::Rollover); }
     if matches.opt_present("s") { opts.actions.push(Action::Show); };
+    opts.search = matches.opt_str("search");
+    if matches.opt_present("I") { opts.ignore_case = true; }
+    if matches.opt_present("regex") { opts.regex = true; }
     if matches.opt_present("t") { opts.actions.push(Action::Today); }
     if matches.opt_present("T") { opts.actions.push(Action::TodayOnly); }
+    // '--to' is the new date for '--postpone' and the tui, and the end of
+    // the agenda otherwise
+    if let Some(to) = matches.opt_str("to") {
+        if matches.opt_present("p") || matches.opt_present("tui") {
+            opts.postpone_to = Some(to);
+        } else {
+            opts.actions.push(Action::Agenda);
+            opts.agenda_to = Some(to);
+        }
+    }
+    if let Some(tag) = matches.opt_str("tag") {
+        opts.actions.push(Action::Tag);
+        opts.tag = Some(tag);
+    }
+    if matches.opt_present("tui") { opts.actions.push(Action::Tui); }
+    if matches.opt_present("undo") {
+        opts.actions.push(Action::Undo);
+        // accept both '--undo=N' and '--undo N'
+        let count = matches.opt_str("undo").or(matches.free.first().cloned());
+        if let Some(count) = count {
+            match count.parse::<usize>() {
+                Ok(i) if i >= 1 => { opts.undo_count = i; },
+                _   => {
+                    let err_msg =
+                        format!("Invalid '--undo' argument '{}': must be a \
+                                number of at least 1", count);
+                    return Err(Error::new(ErrorKind::Other, err_msg));
+                },
+            };
+        }
+    }
     if matches.opt_present("v") { opts.actions.push(Action::Version); }
+    if matches.opt_present("watch") { opts.watch = true; }
+    if matches.opt_present("w") { opts.actions.push(Action::Week); }
+    if matches.opt_present("with-blocked") { opts.with_blocked = true; }
+    if matches.opt_present("W") { opts.workday = true; }
     if matches.opt_present("X") { opts.actions.push(Action::Delete); }
+    if matches.opt_present("y") { opts.yes = true; }
+    // the item is given to the action itself or with -i, and is looked up
+    // once the items are read. Long options take an optional argument only
+    // as '--done=ITEM', so '--done ITEM' leaves it free.
+    let item_opts = ["bulk-edit", "check-step", "done", "e", "history", "p",
+                     "s", "X"];
+    let free = if item_opts.iter().any(|&name| matches.opt_present(name)) {
+        matches.free.first().cloned()
+    } else {
+        None
+    };
+    opts.selector = item_opts.iter()
+                             .filter_map(|&name| matches.opt_str(name))
+                             .next()
+                             .or(matches.opt_str("i"))
+                             .or(free);
+    // with another action the search selects the items for it
+    if opts.search.is_some() && opts.actions.len() == 0 {
+        opts.actions.push(Action::Search);
+    }
     opts.actions.sort();
     opts.actions.dedup();
     Ok(opts)
@@ -0,0 +1,19 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+#[derive(Clone,Debug,PartialEq)]
+pub enum Priority {
+    High,
+    Normal,
+    Low,
+}
+
+
+pub fn parse_priority_val(val: &str) -> Option<Priority> {
+    match val {
+        "high"      => Some(Priority::High),
+        "normal"    => Some(Priority::Normal),
+        "low"       => Some(Priority::Low),
+        _           => None,
+    }
+}
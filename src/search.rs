@@ -0,0 +1,130 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::rc::Rc;
+
+use regex::{self, Regex, RegexBuilder};
+
+use action;
+use color;
+use error::{TodoError, TodoErrorKind, TodoResult};
+use opt::Opt;
+use todo_item::TodoItem;
+
+// lines up with the headings printed by action::print_item
+const INDENT: &'static str = "               ";
+// characters shown around the first match of a long line
+const SNIPPET_CONTEXT: usize = 30;
+
+
+/// Build the regex for the `--search` pattern: the pattern as such with
+/// `--regex`, otherwise as plain text, in any case with `--ignore-case`.
+pub fn pattern(opt: &Opt) -> TodoResult<Regex> {
+    let pattern = opt.search.as_ref().map_or("", |p| &p[..]);
+    let source = if opt.regex { pattern.to_string() }
+                 else { regex::escape(pattern) };
+    RegexBuilder::new(&source).case_insensitive(opt.ignore_case)
+                              .build()
+                              .map_err(|e| {
+        let err_msg = format!("Invalid search pattern '{}': {}", pattern, e);
+        TodoError::new(TodoErrorKind::Other, err_msg)
+    })
+}
+
+
+/// The lines of an item that are searched: the heading, the attrs as
+/// "key: value" and the lines of the body.
+fn item_lines(item: &TodoItem) -> Vec<String> {
+    let mut lines = vec![item.heading.clone()];
+    for attr in &item.attrs {
+        lines.push(format!("{}: {}", attr.key, attr.value));
+    }
+    if let Some(ref body) = item.body {
+        lines.extend(body.lines().map(|l| l.to_string()));
+    }
+    lines
+}
+
+
+/// Get the IDs of the items that match the `--search` pattern.
+pub fn select_items(opt: &Opt, items: &Vec<Rc<TodoItem>>)
+                    -> TodoResult<Vec<i32>> {
+    let re = try!(pattern(opt));
+    let ids: Vec<i32> = items.iter().filter(|item| {
+        item_lines(item).iter().any(|line| re.is_match(line))
+    }).map(|item| item.id).collect();
+    if ids.len() == 0 {
+        let err_msg = format!("No item matches '{}'",
+                              opt.search.as_ref().unwrap());
+        return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+    }
+    Ok(ids)
+}
+
+
+/// Print the items that match the `--search` pattern with their stable IDs,
+/// and the matching lines of each with the matches highlighted.
+pub fn search(opt: &Opt, items: &Vec<Rc<TodoItem>>) -> TodoResult<()> {
+    let re = try!(pattern(opt));
+    let mut found = 0;
+    for item in items {
+        let lines: Vec<String> = item_lines(item).into_iter()
+                                                 .filter(|l| re.is_match(l))
+                                                 .collect();
+        if lines.len() == 0 {
+            continue;
+        }
+        found += 1;
+        action::print_item(opt, item);
+        println!("{}stable id: {}", INDENT, item.stable_id());
+        for line in &lines {
+            println!("{}{}", INDENT, snippet(opt, &re, line));
+        }
+    }
+    if found == 0 {
+        println!("No items match '{}'.", opt.search.as_ref().unwrap());
+    }
+    Ok(())
+}
+
+
+/// Cut a long `line` around its first match, and highlight the matches.
+fn snippet(opt: &Opt, re: &Regex, line: &str) -> String {
+    let line = line.trim();
+    let (start, end) = match re.find(line) {
+        Some(m) => (m.start(), m.end()),
+        None    => (0, 0),
+    };
+    let from = floor_char(line, start.saturating_sub(SNIPPET_CONTEXT));
+    let to = floor_char(line, end + SNIPPET_CONTEXT);
+    let part = &line[from..to];
+
+    let mut text = String::new();
+    if from > 0 {
+        text.push_str("...");
+    }
+    let mut last = 0;
+    for m in re.find_iter(part) {
+        text.push_str(&part[last..m.start()]);
+        text.push_str(&color::paint(opt, &opt.theme.matched, m.as_str()));
+        last = m.end();
+    }
+    text.push_str(&part[last..]);
+    if to < line.len() {
+        text.push_str("...");
+    }
+    text
+}
+
+
+/// Move byte index `i` of `s` back to the start of a character, or to the
+/// end of `s`.
+fn floor_char(s: &str, mut i: usize) -> usize {
+    if i >= s.len() {
+        return s.len();
+    }
+    while !s.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
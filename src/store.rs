@@ -0,0 +1,434 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::UNIX_EPOCH;
+
+#[cfg(unix)]
+use libc;
+
+use error::{TodoError, TodoErrorKind, TodoResult};
+use watch::{Watch, Watcher};
+
+const CACHE_FILE: &'static str = ".cache";
+const LOCK_FILE: &'static str = ".lock";
+const TRASH_DIR: &'static str = ".trash";
+
+
+/// Storage for todo items. Every item is identified by a key, which is also
+/// stored in TodoItem::filename. Keys are names that only mean something to
+/// the store. The store only moves the raw contents of the items around,
+/// parsing them is up to TodoItem. Items are loaded from several threads at
+/// once.
+///
+/// The files of todo's own, like the journal and the templates, are kept in
+/// the store too, under names that are not listed as items.
+pub trait Store: Sync {
+    /// List the keys of all items, in the order item IDs are given in.
+    fn list(&self) -> TodoResult<Vec<String>>;
+
+    /// Read the contents of item `key`.
+    fn load(&self, key: &str) -> TodoResult<String>;
+
+    /// Read the header of item `key`: the heading and the attrs, up to the
+    /// blank line before the body. The lines of the body are only passed to
+    /// `body_line` one at a time, without keeping them. Stores that can not
+    /// stream read the whole item.
+    fn load_header(&self, key: &str, body_line: &mut dyn FnMut(&str))
+                   -> TodoResult<String> {
+        let contents = try!(self.load(key));
+        let mut header = String::new();
+        let mut lines = contents.split_terminator('\n');
+        while let Some(line) = lines.next() {
+            if line.trim_end_matches('\r').len() == 0 {
+                break;
+            }
+            header.push_str(line);
+            header.push('\n');
+        }
+        for line in lines {
+            body_line(line);
+        }
+        Ok(header)
+    }
+
+    /// Write the contents of item `key`, creating the item if needed. Readers
+    /// see either the old or the new contents, never a mix.
+    fn save(&self, key: &str, contents: &str) -> TodoResult<()>;
+
+    /// Write the new item `key`, unless an item with that key already
+    /// exists. Returns whether the item was written. Like `save`, readers
+    /// never see the item half written.
+    fn create(&self, key: &str, contents: &str) -> TodoResult<bool>;
+
+    /// Get the key for a new item called `name`, which is made of letters,
+    /// digits and dashes. Stores whose keys are names use it as it is.
+    fn new_key(&self, name: &str) -> String {
+        name.to_string()
+    }
+
+    /// Read todo's own file `name`, or None if there is no such file.
+    fn load_meta(&self, name: &str) -> TodoResult<Option<String>>;
+
+    /// Add `text` to the end of todo's own file `name`, creating the file if
+    /// needed. The text is written at once, so that the texts appended by
+    /// other todo processes at the same time do not get mixed with it.
+    fn append_meta(&self, name: &str, text: &str) -> TodoResult<()>;
+
+    /// Give item `from` the key `to`, failing if there already is an item
+    /// `to`. The item is never missing in between.
+    fn rename(&self, from: &str, to: &str) -> TodoResult<()>;
+
+    /// Remove item `key` for good.
+    fn delete(&self, key: &str) -> TodoResult<()>;
+
+    /// Put item `key` away so that it can still be recovered, and return
+    /// where it went. Stores without a trash delete the item and return None.
+    fn trash(&self, key: &str) -> TodoResult<Option<String>> {
+        try!(self.delete(key));
+        Ok(None)
+    }
+
+    /// Get a watcher that reports changed items by their keys.
+    fn watch(&self) -> TodoResult<Box<dyn Watch>>;
+
+    /// Get a stamp of item `key` that changes whenever the item does, so
+    /// that parsed items can be cached. Stores that can not tell return None.
+    fn stamp(&self, _key: &str) -> Option<Stamp> {
+        None
+    }
+
+    /// Get the file parsed items are cached in, if the store has one.
+    fn cache_path(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Lock the whole store for an operation that changes several items.
+    /// The lock is advisory and held until the returned Lock is dropped.
+    fn lock(&self) -> TodoResult<Lock> {
+        Ok(Lock { file: None })
+    }
+}
+
+
+/// An advisory lock on a store, released when dropped.
+pub struct Lock {
+    #[allow(dead_code)]
+    file:   Option<File>,
+}
+
+
+/// The modification time in nanoseconds since the epoch, the size and the
+/// inode of an item file.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Stamp {
+    pub mtime:  i64,
+    pub size:   u64,
+    pub inode:  u64,
+}
+
+
+/// The default store: one file per item in the todo dir. Keys are the names
+/// of the files. Hidden files are for todo's own use and are not items, and
+/// the names of todo's own files are paths in the todo dir.
+pub struct DirStore {
+    dir:    PathBuf,
+}
+
+
+impl DirStore {
+    pub fn new(dir: &Path) -> DirStore {
+        DirStore {
+            dir:    dir.to_path_buf(),
+        }
+    }
+
+
+    #[cfg(feature = "sqlite")]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+
+    /// Get the path of item or file `key`.
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(key)
+    }
+}
+
+
+impl Store for DirStore {
+    fn list(&self) -> TodoResult<Vec<String>> {
+        let mut files = Vec::new();
+        // get file list
+        if try!(fs::metadata(&self.dir)).is_dir() {
+            for entry in try!(fs::read_dir(&self.dir)) {
+                match entry {
+                    Err(err) => print_err!("Error: {}", err),
+                    Ok(dirent) => match dirent.file_type() {
+                        Err(err) => print_err!("could not find file type for \
+                                               file '{}'",
+                                               err),
+                        Ok(ft)   => if ft.is_file() &&
+                                       !is_hidden(&dirent.path()) {
+                            match dirent.file_name().to_str() {
+                                Some(name)  => files.push(name.to_string()),
+                                None        => {
+                                    print_err!("Invalid file name '{:?}'",
+                                               dirent.path());
+                                },
+                            };
+                        },
+                    },
+                }
+            }
+        }
+        Ok(files)
+    }
+
+
+    fn load(&self, key: &str) -> TodoResult<String> {
+        let mut contents = String::new();
+        let mut fd = try!(File::open(self.path(key)));
+        try!(fd.read_to_string(&mut contents));
+        Ok(contents)
+    }
+
+
+    /// The body is read line by line, so that it is never in memory as a
+    /// whole. Invalid UTF-8 in it is replaced, as its text is not kept.
+    fn load_header(&self, key: &str, body_line: &mut dyn FnMut(&str))
+                   -> TodoResult<String> {
+        let mut reader = BufReader::new(try!(File::open(self.path(key))));
+        let mut header = String::new();
+        loop {
+            let start = header.len();
+            if try!(reader.read_line(&mut header)) == 0 {
+                return Ok(header);
+            }
+            if header[start..].trim_end_matches(&['\n', '\r'][..]).len() == 0 {
+                header.truncate(start);
+                break;
+            }
+        }
+
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if try!(reader.read_until(b'\n', &mut line)) == 0 {
+                break;
+            }
+            let text = String::from_utf8_lossy(&line);
+            body_line(text.trim_end_matches(&['\n', '\r'][..]));
+        }
+        Ok(header)
+    }
+
+
+    /// The contents are written to a hidden file next to the item, synced
+    /// to disk and renamed over the item.
+    fn save(&self, key: &str, contents: &str) -> TodoResult<()> {
+        let path = self.path(key);
+        let tmp = try!(tmp_path(&path));
+        let written = File::create(&tmp).and_then(|mut fd| {
+            try!(fd.write_all(contents.as_bytes()));
+            if let Ok(meta) = fs::metadata(&path) {
+                try!(fd.set_permissions(meta.permissions()));
+            }
+            fd.sync_all()
+        }).and_then(|_| fs::rename(&tmp, &path));
+        if let Err(err) = written {
+            let _ = fs::remove_file(&tmp);
+            return Err(From::from(err));
+        }
+
+        // make the rename itself durable
+        if let Some(dir) = path.parent() {
+            let _ = File::open(dir).and_then(|fd| fd.sync_all());
+        }
+        Ok(())
+    }
+
+
+    /// The contents are written to a hidden file like in `save`, which is
+    /// then linked to the item. Unlike renaming, linking fails if the item
+    /// exists.
+    fn create(&self, key: &str, contents: &str) -> TodoResult<bool> {
+        let path = self.path(key);
+        let tmp = try!(tmp_path(&path));
+        let written = File::create(&tmp).and_then(|mut fd| {
+            try!(fd.write_all(contents.as_bytes()));
+            fd.sync_all()
+        }).and_then(|_| fs::hard_link(&tmp, &path));
+        let _ = fs::remove_file(&tmp);
+        match written {
+            Ok(())  => {},
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                return Ok(false);
+            },
+            Err(err)    => return Err(From::from(err)),
+        };
+
+        if let Some(dir) = path.parent() {
+            let _ = File::open(dir).and_then(|fd| fd.sync_all());
+        }
+        Ok(true)
+    }
+
+
+    fn load_meta(&self, name: &str) -> TodoResult<Option<String>> {
+        let mut contents = String::new();
+        match File::open(self.path(name)) {
+            Ok(mut fd)  => { try!(fd.read_to_string(&mut contents)); },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(None);
+            },
+            Err(e)      => { return Err(From::from(e)); },
+        };
+        Ok(Some(contents))
+    }
+
+
+    /// The file is opened for appending, so that one write goes to its end
+    /// as a whole, and synced to disk.
+    fn append_meta(&self, name: &str, text: &str) -> TodoResult<()> {
+        let mut fd = try!(OpenOptions::new().create(true).append(true)
+                                            .open(self.path(name)));
+        try!(fd.write_all(text.as_bytes()));
+        try!(fd.sync_data());
+        Ok(())
+    }
+
+
+    /// Link the file to its new name and unlink the old one, as rename(2)
+    /// would replace a file that is already there.
+    fn rename(&self, from: &str, to: &str) -> TodoResult<()> {
+        try!(fs::hard_link(self.path(from), self.path(to)));
+        try!(fs::remove_file(self.path(from)));
+        Ok(())
+    }
+
+
+    fn delete(&self, key: &str) -> TodoResult<()> {
+        try!(fs::remove_file(self.path(key)));
+        Ok(())
+    }
+
+
+    /// Move the file to the .trash directory inside the todo dir. Files
+    /// already in the trash are not overwritten.
+    fn trash(&self, key: &str) -> TodoResult<Option<String>> {
+        let name = match Path::new(key).file_name().and_then(|n| n.to_str()) {
+            Some(name)  => name.to_string(),
+            None        => {
+                let err_msg = format!("Invalid todo file '{}'", key);
+                return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+            },
+        };
+
+        let trash = self.dir.join(TRASH_DIR);
+        try!(fs::create_dir_all(&trash));
+
+        let mut target = Path::new(TRASH_DIR).join(&name);
+        let mut n = 1;
+        while self.path(&target.to_string_lossy()).exists() {
+            target = Path::new(TRASH_DIR).join(format!("{}.{}", name, n));
+            n += 1;
+        }
+
+        let target = target.to_string_lossy().into_owned();
+        try!(self.rename(key, &target));
+        Ok(Some(self.path(&target).to_string_lossy().into_owned()))
+    }
+
+
+    fn watch(&self) -> TodoResult<Box<dyn Watch>> {
+        Ok(Box::new(Watcher::new(&self.dir)))
+    }
+
+
+    fn stamp(&self, key: &str) -> Option<Stamp> {
+        let meta = match fs::metadata(self.path(key)) {
+            Ok(meta)    => meta,
+            Err(_)      => { return None; },
+        };
+        let mtime = match meta.modified().ok()
+                              .and_then(|t| t.duration_since(UNIX_EPOCH).ok()) {
+            Some(d) => d.as_secs() as i64 * 1_000_000_000 +
+                       d.subsec_nanos() as i64,
+            None    => { return None; },
+        };
+        Some(Stamp {
+            mtime:  mtime,
+            size:   meta.len(),
+            inode:  inode(&meta),
+        })
+    }
+
+
+    fn cache_path(&self) -> Option<PathBuf> {
+        Some(self.dir.join(CACHE_FILE))
+    }
+
+
+    /// Lock the .lock file in the todo dir with flock(2), waiting for other
+    /// todo processes to let go of it.
+    #[cfg(unix)]
+    fn lock(&self) -> TodoResult<Lock> {
+        use std::os::unix::io::AsRawFd;
+
+        let path = self.dir.join(LOCK_FILE);
+        let file = try!(OpenOptions::new().create(true).write(true)
+                                           .open(&path));
+        let fd = file.as_raw_fd();
+        if unsafe { libc::flock(fd, libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            print_err!("Waiting for the lock on {}...", path.display());
+            while unsafe { libc::flock(fd, libc::LOCK_EX) } != 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(From::from(err));
+                }
+            }
+        }
+        Ok(Lock { file: Some(file) })
+    }
+}
+
+
+/// Get the hidden file item `path` is written to before it is put in place.
+fn tmp_path(path: &Path) -> TodoResult<PathBuf> {
+    match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => {
+            Ok(dir.join(format!(".{}.tmp{}", name.to_string_lossy(),
+                                process::id())))
+        },
+        _   => {
+            let err_msg = format!("Invalid todo file '{}'", path.display());
+            Err(TodoError::new(TodoErrorKind::Other, err_msg))
+        },
+    }
+}
+
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.starts_with('.'))
+        .unwrap_or(false)
+}
+
+
+#[cfg(unix)]
+fn inode(meta: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+
+#[cfg(not(unix))]
+fn inode(_: &fs::Metadata) -> u64 {
+    0
+}
@@ -0,0 +1,67 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::env;
+use std::path::Path;
+
+use error::{TodoError, TodoErrorKind, TodoResult};
+use opt::Opt;
+use store::Store;
+use util;
+
+const TEMPLATE_DIR: &'static str = ".templates";
+// used by '--new' without a template name, unless .templates/default exists
+const DEFAULT_TEMPLATE: &'static str = "default";
+
+// templates todo knows without a file in the template dir
+const BUILTIN_TEMPLATES: &'static [(&'static str, &'static str)] = &[
+    ("bug",     "Fix \ndate: {{today}}\npriority: high\n\n\
+                 Reported by {{user}} on {{today}}.\n\n\
+                 Steps to reproduce:\n\nExpected:\n\nActual:\n"),
+    ("default", "\ndate: {{today}}\n\n"),
+    ("errand",  "\ndate: {{today}}\npriority: low\n\nWhere:\n"),
+    ("meeting", "Meeting: \ndate: {{tomorrow}}\n\nWith:\n\nAgenda:\n- \n\n\
+                 Notes:\n"),
+];
+
+
+/// Get template `name`, or the default one, with its placeholders filled
+/// in. A template in the template dir overrides a built-in one.
+pub fn render(opt: &Opt, store: &dyn Store, name: Option<&str>)
+              -> TodoResult<String> {
+    let name = name.unwrap_or(DEFAULT_TEMPLATE);
+    // only the files right in the template dir are templates
+    let stored = if is_plain_name(name) {
+        try!(store.load_meta(&format!("{}/{}", TEMPLATE_DIR, name)))
+    } else {
+        None
+    };
+    let template = match stored {
+        Some(template)  => template,
+        None            => {
+            match BUILTIN_TEMPLATES.iter().find(|&&(n, _)| n == name) {
+                Some(&(_, builtin)) => builtin.to_string(),
+                None                => {
+                    let err_msg = format!("No template '{}' in {}", name,
+                                          TEMPLATE_DIR);
+                    return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+                },
+            }
+        },
+    };
+
+    let today = opt.clock.today();
+    let tomorrow = util::add_days(&today, 1);
+    let user = env::var("USER").or(env::var("LOGNAME"))
+                               .unwrap_or(String::new());
+    Ok(template.replace("{{today}}", &try!(util::date_to_str(&today)))
+               .replace("{{tomorrow}}", &try!(util::date_to_str(&tomorrow)))
+               .replace("{{user}}", &user))
+}
+
+
+/// Check that `name` is a file name without a directory in it.
+fn is_plain_name(name: &str) -> bool {
+    !name.contains('/') && !name.contains('\\') && !name.contains("..") &&
+        Path::new(name).file_name().map_or(false, |n| n == name)
+}
@@ -2,37 +2,49 @@
 // Licensed under the 2-clause BSD license, see LICENSE for details.
 
 use std::cmp::Ordering;
-use std::fs::File;
-use std::io::{Error, ErrorKind, Read};
+use std::io::{Error, ErrorKind};
 use std::path::Path;
 use std::str::Lines;
 
-use time;
 use time::Tm;
 
 use attr::Attr;
 use error::TodoResult;
+use priority::{Priority, parse_priority_val};
 use status::{Status, parse_status_val};
+use store::Stamp;
 use util;
 
-macro_rules! try_opt(
-    ($e:expr) => (match $e { Some(e) => e, None => return None })
-);
-
-// TODO: make body an Option
+/// A todo item. The body is None when only the header of the item was read.
+/// The stamp is that of the item when it was read, if the store has them.
+/// The checklist is the number of done steps and all steps in the body.
 #[derive(Clone,Debug)]
 pub struct TodoItem {
     pub attrs:      Vec<Attr>,
-    pub body:       String,
+    pub body:       Option<String>,
+    pub checklist:  (usize, usize),
     pub date:       Option<Tm>,
     pub filename:   String,
     pub heading:    String,
     pub id:         i32,
+    pub postponed:  u32,
+    pub priority:   Option<Priority>,
+    pub stamp:      Option<Stamp>,
     pub status:     Option<Status>,
 }
 
 
 impl TodoItem {
+    /// An ID that stays the same as long as the file of the item is not
+    /// renamed, unlike `id` that depends on the other items.
+    pub fn stable_id(&self) -> String {
+        let name = Path::new(&self.filename).file_name()
+                                            .and_then(|n| n.to_str())
+                                            .unwrap_or("");
+        format!("{:08x}", util::fnv1a(name.as_bytes()) as u32)
+    }
+
+
     pub fn get_date_str(&self) -> Option<String> {
         // change Tm to str
         self.date.map(|date| util::date_to_str(&date).ok()).unwrap_or(None)
@@ -42,33 +54,158 @@ impl TodoItem {
     pub fn new(id: i32, filename: String) -> TodoItem {
         TodoItem {
             attrs:      Vec::new(),
-            body:       "".to_string(),
+            body:       None,
+            checklist:  (0, 0),
             date:       None,
             filename:   filename,
             heading:    "".to_string(),
             id:         id,
+            postponed:  0,
+            priority:   None,
+            stamp:      None,
             status:     None,
         }
     }
 
 
-    pub fn new_from_file(file: &Path, id: i32) -> TodoResult<TodoItem> {
-        let filename = file.to_str().unwrap();
+    /// Parse the item stored under `key` from its `contents`.
+    pub fn new_from_str(key: &str, contents: &str, id: i32)
+                        -> TodoResult<TodoItem> {
+        let filename = key;
 
         // init temporary TodoItem
         let mut item = TodoItem::new(id, filename.to_string());
 
-        let file_contents = try!(get_file_contents(&file));
-        let mut line_it = file_contents.lines();
+        let mut line_it = contents.lines();
+
+        let mut errors = Vec::new();
+        item.heading = try!(get_heading(&mut line_it));
+        let attrs = get_attrs(&mut line_it, &mut errors);
+        let body = get_body(&mut line_it);
+        item.checklist = count_steps(&body);
+        item.body = Some(body);
+
+        parse_attrs(&attrs, &mut item, &mut errors);
+        item.attrs = attrs;
+        print_errors(filename, &errors);
 
+        Ok(item)
+    }
+
+
+    /// Parse the item stored under `key` from its header, without the body.
+    pub fn new_from_header(key: &str, header: &str, id: i32)
+                           -> TodoResult<TodoItem> {
+        let mut item = TodoItem::new(id, key.to_string());
+        let mut line_it = header.lines();
+
+        let mut errors = Vec::new();
         item.heading = try!(get_heading(&mut line_it));
-        let attrs = get_attrs(filename, &mut line_it);
-        get_body(&mut line_it, &mut item.body);
+        let attrs = get_attrs(&mut line_it, &mut errors);
 
-        parse_attrs(&attrs, &mut item);
+        parse_attrs(&attrs, &mut item, &mut errors);
+        item.attrs = attrs;
+        print_errors(key, &errors);
 
         Ok(item)
     }
+
+
+    /// Build the item stored under `key` from its already split parts.
+    pub fn new_from_parts(key: &str, id: i32, heading: String,
+                          attrs: Vec<Attr>, body: Option<String>) -> TodoItem {
+        let mut item = TodoItem::new(id, key.to_string());
+        item.heading = heading;
+        item.checklist = body.as_ref().map_or((0, 0), |b| count_steps(b));
+        item.body = body;
+        let mut errors = Vec::new();
+        parse_attrs(&attrs, &mut item, &mut errors);
+        item.attrs = attrs;
+        print_errors(key, &errors);
+        item
+    }
+}
+
+
+/// A step of a checklist in the body of an item, a line like "- [ ] Pack"
+/// or "- [x] Pack".
+#[derive(Clone,Debug)]
+pub struct Step {
+    pub done:       bool,
+    /// The line of the body the step is on, counting from 0.
+    pub line:       usize,
+    /// The byte offset of the mark between the brackets in the line.
+    pub mark:       usize,
+    pub text:       String,
+}
+
+
+/// Parse the checklist steps out of `body`. Steps may be indented and use
+/// '*' for the bullet, and "[X]" is done as well as "[x]".
+pub fn parse_checklist(body: &str) -> Vec<Step> {
+    body.lines().enumerate().filter_map(|(i, line)| parse_step(i, line))
+                            .collect()
+}
+
+
+/// Parse line `i` of a body as a checklist step, if it is one.
+fn parse_step(i: usize, line: &str) -> Option<Step> {
+    let text = line.trim_start();
+    let indent = line.len() - text.len();
+    let bytes = text.as_bytes();
+    if bytes.len() < 5 || (bytes[0] != b'-' && bytes[0] != b'*') ||
+       bytes[1] != b' ' || bytes[2] != b'[' || bytes[4] != b']' {
+        return None;
+    }
+    let done = match bytes[3] {
+        b' '            => false,
+        b'x' | b'X'     => true,
+        _               => return None,
+    };
+    Some(Step {
+        done:       done,
+        line:       i,
+        mark:       indent + 3,
+        text:       text[5..].trim().to_string(),
+    })
+}
+
+
+/// Add the step on `line` of a body, if there is one, to the `checklist`
+/// counts, for counting the steps without keeping the body.
+pub fn count_step(checklist: &mut (usize, usize), line: &str) {
+    if let Some(step) = parse_step(0, line) {
+        checklist.1 += 1;
+        if step.done {
+            checklist.0 += 1;
+        }
+    }
+}
+
+
+fn count_steps(body: &str) -> (usize, usize) {
+    let mut checklist = (0, 0);
+    for line in body.lines() {
+        count_step(&mut checklist, line);
+    }
+    checklist
+}
+
+
+/// Return the problems parsing `contents` as an item would report, like an
+/// empty heading or a bad date.
+pub fn check_contents(contents: &str) -> Vec<String> {
+    let mut item = TodoItem::new(0, String::new());
+    let mut line_it = contents.lines();
+    let mut errors = Vec::new();
+
+    if let Err(err) = get_heading(&mut line_it) {
+        errors.push(err.to_string());
+        return errors;
+    }
+    let attrs = get_attrs(&mut line_it, &mut errors);
+    parse_attrs(&attrs, &mut item, &mut errors);
+    errors
 }
 
 
@@ -76,19 +213,13 @@ impl Eq for TodoItem {}
 
 
 impl Ord for TodoItem {
-    // TODO: compare Tm fields instead of strs
     fn cmp(&self, other: &Self) -> Ordering {
-        let sd = self.get_date_str();
-        let od = other.get_date_str();
-
-        if sd.is_none() && od.is_none() {
-            return Ordering::Equal;
-        } else if sd.is_some() && od.is_none() {
-            return Ordering::Less;
-        } else if sd.is_none() && od.is_some() {
-            return Ordering::Greater;
-        } else {
-            return sd.unwrap().cmp(&od.unwrap());
+        let day = |date: &Tm| (date.tm_year, date.tm_mon, date.tm_mday);
+        match (self.date.as_ref(), other.date.as_ref()) {
+            (None, None)        => Ordering::Equal,
+            (Some(_), None)     => Ordering::Less,
+            (None, Some(_))     => Ordering::Greater,
+            (Some(sd), Some(od))=> day(sd).cmp(&day(od)),
         }
     }
 }
@@ -117,7 +248,7 @@ impl PartialOrd for TodoItem {
 }
 
 
-fn get_attrs(file: &str, line_it: &mut Lines) -> Vec<Attr> {
+fn get_attrs(line_it: &mut Lines, errors: &mut Vec<String>) -> Vec<Attr> {
     let mut attrs: Vec<Attr> = Vec::new();
     while let Some(line) = line_it.next() {
         // check if line is body separator
@@ -127,7 +258,7 @@ fn get_attrs(file: &str, line_it: &mut Lines) -> Vec<Attr> {
 
         match Attr::new_from_line(line) {
             Ok(attr)    => { attrs.push(attr); },
-            Err(err)    => { print_err!("{}: {}", file, err); },
+            Err(err)    => { errors.push(err.to_string()); },
         };
     }
 
@@ -135,20 +266,14 @@ fn get_attrs(file: &str, line_it: &mut Lines) -> Vec<Attr> {
 }
 
 
-fn get_body(line_it: &mut Lines, body: &mut String) {
+fn get_body(line_it: &mut Lines) -> String {
     // TODO: only add '\n' if not last line
+    let mut body = String::new();
     while let Some(line) = line_it.next() {
         body.push_str(&line);
         body.push('\n');
     }
-}
-
-
-fn get_file_contents(file: &Path) -> Result<String, Error> {
-         let mut contents = String::new();
-         let mut fd = try!(File::open(file));
-         try!(fd.read_to_string(&mut contents));
-         Ok(contents)
+    body
 }
 
 
@@ -158,7 +283,6 @@ fn get_heading(line_it: &mut Lines) -> Result<String, Error> {
             if line.trim().len() > 0 {
                 Ok(line.trim().to_string())
             } else {
-                println!("line '{}' is too short", line);
                 return Err(Error::new(ErrorKind::Other, "Heading empty"))
             }
         },
@@ -167,27 +291,57 @@ fn get_heading(line_it: &mut Lines) -> Result<String, Error> {
 }
 
 
-fn parse_attrs(attrs: &Vec<Attr>, item: &mut TodoItem) {
+fn print_errors(file: &str, errors: &Vec<String>) {
+    for err in errors {
+        print_err!("{}: {}", file, err);
+    }
+}
+
+
+fn parse_attrs(attrs: &Vec<Attr>, item: &mut TodoItem,
+               errors: &mut Vec<String>) {
     for attr in attrs {
         match &attr.key[..] {
             "date"      => {
-                match parse_date(&attr.value) {
+                match util::str_to_date(&attr.value) {
                     Ok(date)    => item.date = Some(date),
-                    Err(err)    => print_err!("{}: {}", item.filename, err),
+                    Err(err)    => {
+                        errors.push(format!("invalid date '{}': {}",
+                                            attr.value, err));
+                    },
                 };
             }
+            "postponed" => {
+                match attr.value.parse::<u32>() {
+                    Ok(n)       => item.postponed = n,
+                    Err(err)    => {
+                        errors.push(format!("invalid postponed count '{}': {}",
+                                            attr.value, err));
+                    },
+                };
+            },
+            "priority"  => {
+                item.priority = parse_priority_val(&attr.value);
+                if item.priority.is_none() {
+                    errors.push(format!("invalid priority '{}'",
+                                        attr.value));
+                }
+            },
+            // the items this one waits for, and the one it is a subtask of
+            "depends"   => {},
+            "parent"    => {},
+            // the items this one links to, like "[[heading]]" in the body
+            "related"   => {},
+            // where a captured item came from
+            "source"    => {},
+            "tags"      => {},
             "status"    => {
                 item.status = parse_status_val(&attr.value);
             },
             _           => {
-                print_err!("{}: invalid attr: key='{}', value='{}'",
-                           item.filename, attr.key, attr.value )
+                errors.push(format!("invalid attr: key='{}', value='{}'",
+                                    attr.key, attr.value));
             },
         }
     }
 }
-
-
-fn parse_date(date_str: &str) -> Result<Tm, time::ParseError> {
-    time::strptime(date_str, "%Y-%m-%d")
-}
@@ -1,13 +1,29 @@
 // Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
 // Licensed under the 2-clause BSD license, see LICENSE for details.
 
-use std::fs;
-use std::io;
+use std::cmp;
+use std::collections::HashMap;
+use std::path::Path;
 use std::rc::Rc;
-use std::path::{Path, PathBuf};
+use std::thread;
 
+use attr::Attr;
+use cache::Cache;
+use error::{TodoError, TodoErrorKind, TodoResult};
 use status::Status;
-use todo_item::TodoItem;
+use store::{Stamp, Store};
+use todo_item::{self, TodoItem};
+
+// smallest number of files worth a thread of its own
+const ITEMS_PER_THREAD: usize = 256;
+// shortest stable ID prefix that selects an item
+const MIN_ID_PREFIX: usize = 4;
+// the items an item waits for, and the item it is a subtask of. Items are
+// referred to by their stable IDs or file names.
+pub const DEPENDS_ATTR: &'static str = "depends";
+pub const PARENT_ATTR: &'static str = "parent";
+pub const RELATED_ATTR: &'static str = "related";
+pub const TAGS_ATTR: &'static str = "tags";
 
 
 pub fn get_dateless_items(items: &Vec<Rc<TodoItem>>)
@@ -22,26 +38,6 @@ pub fn get_dateless_items(items: &Vec<Rc<TodoItem>>)
 }
 
 
-pub fn get_files_in_dir(dir: &Path) -> io::Result<Vec<PathBuf>> {
-    let mut files = Vec::new();
-    // get file list
-    if try!(fs::metadata(dir)).is_dir() {
-        for entry in fs::read_dir(dir).unwrap() {
-            match entry {
-                Err(err) => println!("Error: {}", err),
-                Ok(dirent) => match dirent.file_type() {
-                    Err(err) => println!("could not find file type for \
-                                         file '{}'",
-                                         err),
-                    Ok(ft)   => if ft.is_file() { files.push(dirent.path()) },
-                },
-            }
-        }
-    }
-    Ok(files)
-}
-
-
 pub fn get_item_by_id(items: &Vec<Rc<TodoItem>>, i: i32)
                      -> Option<Rc<TodoItem>> {
     for item in items {
@@ -70,12 +66,13 @@ pub fn get_items_on_date(items: &Vec<Rc<TodoItem>>, date_str: &str)
 }
 
 
-pub fn get_items_after(items: &Vec<Rc<TodoItem>>, date_str: &str)
-                      -> Vec<Rc<TodoItem>> {
+/// Get items dated between `from_str` and `to_str`, both inclusive.
+pub fn get_items_between(items: &Vec<Rc<TodoItem>>, from_str: &str,
+                         to_str: &str) -> Vec<Rc<TodoItem>> {
     let mut list: Vec<Rc<TodoItem>> = Vec::new();
     for item in items {
         if let Some(i_date) = item.get_date_str() {
-            if &i_date[..] > date_str {
+            if &i_date[..] >= from_str && &i_date[..] <= to_str {
                 list.push(item.clone());
             }
         }
@@ -98,20 +95,100 @@ pub fn get_items_before(items: &Vec<Rc<TodoItem>>, date_str: &str)
 }
 
 
-pub fn get_todo_items(path: &Path) -> io::Result<Vec<Rc<TodoItem>>> {
-    let mut items: Vec<Rc<TodoItem>> = Vec::new();
-    let files = try!(get_files_in_dir(path));
-    for (id, file) in (1..).zip(files.iter()) {
-        match TodoItem::new_from_file(&file, id) {
-            Ok(i)   => items.push(Rc::new(i)),
-            Err(err)=> print_err!("Could not load todo file '{:?}': {}",
-                                  file, err),
+/// Get all todo items in `store`, with their bodies.
+pub fn get_todo_items(store: &dyn Store) -> TodoResult<Vec<Rc<TodoItem>>> {
+    reload_todo_items(store, &Vec::new(), &[], true)
+}
+
+
+/// Get all todo items in `store` without their bodies, which is enough for
+/// the listing views. Only the headers of the items are read, or taken from
+/// the cache for unchanged items, and the bodies are only scanned for the
+/// checklist counts.
+pub fn get_todo_headers(store: &dyn Store) -> TodoResult<Vec<Rc<TodoItem>>> {
+    reload_todo_items(store, &Vec::new(), &[], false)
+}
+
+
+/// Get the todo items in `store`, reusing the already loaded `items` for
+/// keys that are not listed in `changed`. IDs are given in the same way as
+/// in get_todo_items, so they stay in sync with a fresh load. New items are
+/// loaded with their bodies if `bodies` is set.
+pub fn reload_todo_items(store: &dyn Store, items: &Vec<Rc<TodoItem>>,
+                         changed: &[String], bodies: bool)
+                         -> TodoResult<Vec<Rc<TodoItem>>> {
+    let mut loaded: HashMap<&str, &Rc<TodoItem>> = HashMap::new();
+    for item in items {
+        loaded.insert(&item.filename, item);
+    }
+
+    let mut new_items: Vec<Rc<TodoItem>> = Vec::new();
+    let mut to_load: Vec<(i32, &str)> = Vec::new();
+    let keys = try!(store.list());
+    for (id, key) in (1..).zip(keys.iter()) {
+        match loaded.get(&key[..]) {
+            Some(item) if !changed.contains(key) => {
+                if item.id == id {
+                    new_items.push((*item).clone());
+                } else {
+                    let mut moved = (***item).clone();
+                    moved.id = id;
+                    new_items.push(Rc::new(moved));
+                }
+            },
+            _   => to_load.push((id, key)),
         };
     };
 
-    // Sort items here, so filtered items will be "automatically" in order too
-    items.sort();
-    Ok(items)
+    // headers of unchanged files come from the cache
+    let mut cache = if bodies { None }
+                    else { store.cache_path().map(|path| Cache::open(&path)) };
+    let mut stamps: HashMap<&str, Stamp> = HashMap::new();
+    if let Some(ref cache) = cache {
+        let mut misses = Vec::new();
+        for (id, key) in to_load {
+            if let Some(stamp) = store.stamp(key) {
+                match cache.get(key, &stamp, id) {
+                    Some(item)  => {
+                        new_items.push(Rc::new(item));
+                        continue;
+                    },
+                    None        => { stamps.insert(key, stamp); },
+                };
+            }
+            misses.push((id, key));
+        }
+        to_load = misses;
+    }
+
+    for (key, result) in load_items(store, &to_load, bodies) {
+        match result {
+            Ok(i)   => {
+                if let (Some(cache), Some(stamp)) = (cache.as_mut(),
+                                                     stamps.get(key)) {
+                    if i.stamp.as_ref() == Some(stamp) {
+                        cache.insert(*stamp, &i);
+                    }
+                }
+                new_items.push(Rc::new(i));
+            },
+            Err(err)=> print_err!("Could not load todo file '{}': {}",
+                                  key, err),
+        };
+    }
+
+    if let Some(ref mut cache) = cache {
+        cache.retain(&keys);
+        if let Err(err) = cache.save() {
+            print_err!("Could not write cache: {}", err);
+        }
+    }
+
+    // Sort items here, so filtered items will be "automatically" in order too.
+    // Items of the same day are in ID order, whatever the cache had.
+    new_items.sort_by_key(|item| item.id);
+    new_items.sort();
+    Ok(new_items)
 }
 
 
@@ -130,7 +207,295 @@ pub fn get_undone_items(items: &Vec<Rc<TodoItem>>) -> Vec<Rc<TodoItem>> {
 }
 
 
+/// Check whether `reference`, the stable ID or the file name of an item,
+/// refers to `item`.
+pub fn refers_to(reference: &str, item: &TodoItem) -> bool {
+    let name = Path::new(&item.filename).file_name()
+                                        .and_then(|n| n.to_str())
+                                        .unwrap_or("");
+    reference == name || reference == item.stable_id()
+}
+
+
+/// Check whether `child` is a subtask of `parent`.
+pub fn is_child_of(child: &TodoItem, parent: &TodoItem) -> bool {
+    child.attrs.iter().any(|attr| {
+        attr.key == PARENT_ATTR && refers_to(&attr.value, parent)
+    })
+}
+
+
+/// Get the subtasks of `parent`.
+pub fn get_children(items: &Vec<Rc<TodoItem>>, parent: &TodoItem)
+                    -> Vec<Rc<TodoItem>> {
+    items.iter().filter(|item| is_child_of(item, parent))
+                .cloned()
+                .collect()
+}
+
+
+/// Count the done subtasks of `parent` and all of them.
+pub fn get_progress(items: &Vec<Rc<TodoItem>>, parent: &TodoItem)
+                    -> (usize, usize) {
+    let children = get_children(items, parent);
+    let done = children.iter().filter(|c| c.status == Some(Status::Done))
+                              .count();
+    (done, children.len())
+}
+
+
+/// Order `group` so that the subtasks in it come right after their parent,
+/// and give each item its depth under the parents. Items whose parent is
+/// not in the group stay where they are.
+pub fn nest(group: &Vec<Rc<TodoItem>>) -> Vec<(Rc<TodoItem>, usize)> {
+    let mut nested: Vec<(Rc<TodoItem>, usize)> = Vec::new();
+    for item in group {
+        if !group.iter().any(|parent| is_child_of(item, parent)) {
+            push_nested(group, item, 0, &mut nested);
+        }
+    }
+    // items that are each other's parents have no root to hang on
+    for item in group {
+        push_nested(group, item, 0, &mut nested);
+    }
+    nested
+}
+
+
+fn push_nested(group: &Vec<Rc<TodoItem>>, item: &Rc<TodoItem>, depth: usize,
+               nested: &mut Vec<(Rc<TodoItem>, usize)>) {
+    if nested.iter().any(|&(ref i, _)| i.id == item.id) {
+        return;
+    }
+    nested.push((item.clone(), depth));
+    for child in group.iter().filter(|c| is_child_of(c, item)) {
+        push_nested(group, child, depth + 1, nested);
+    }
+}
+
+
+/// Find the IDs of the items `selector` refers to. It is a comma separated
+/// list of single items (see `select_item`), ranges of IDs like "7-9" and
+/// filters like "priority=high" that select all items with that attr. A
+/// filter on "tags" selects the items with that tag. A selector that is the
+/// whole heading or the file name of an item selects it even if it has
+/// commas or dashes in it, and a range that selects nothing, like
+/// "2016-02", is looked up as a single item.
+pub fn select_items(items: &Vec<Rc<TodoItem>>, selector: &str)
+                    -> TodoResult<Vec<i32>> {
+    if selector.contains(',') || selector.contains('-') {
+        let whole = selector.trim().to_lowercase();
+        if let Some(item) = items.iter().find(|item| {
+            item.heading.to_lowercase() == whole || refers_to(selector, item)
+        }) {
+            return Ok(vec![item.id]);
+        }
+    }
+
+    let mut ids: Vec<i32> = Vec::new();
+    for part in selector.split(',').map(|p| p.trim()).filter(|p| p.len() > 0) {
+        let mut range = part.splitn(2, '-').map(|n| n.trim().parse::<i32>());
+        let range_ids: Vec<i32> = match (range.next(), range.next()) {
+            (Some(Ok(first)), Some(Ok(last)))   => {
+                items.iter().map(|item| item.id)
+                            .filter(|&id| id >= first && id <= last)
+                            .collect()
+            },
+            _   => Vec::new(),
+        };
+        let part_ids = if range_ids.len() > 0 {
+            range_ids
+        } else if let Some(filter) = parse_filter(part) {
+            items.iter().filter(|item| {
+                has_attr(item, &filter.key, &filter.value)
+            }).map(|item| item.id).collect()
+        } else {
+            vec![try!(select_item(items, part))]
+        };
+        for id in part_ids {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+
+    if ids.len() == 0 {
+        let err_msg = format!("No item matches '{}'", selector);
+        return Err(TodoError::new(TodoErrorKind::Other, err_msg));
+    }
+    Ok(ids)
+}
+
+
+/// Get the "key=value" filters of `selector`, or None if it selects items
+/// some other way too. An item matching any of them is selected.
+#[cfg(feature = "sqlite")]
+pub fn selector_filters(selector: &str) -> Option<Vec<Attr>> {
+    let mut filters: Vec<Attr> = Vec::new();
+    for part in selector.split(',').map(|p| p.trim()).filter(|p| p.len() > 0) {
+        match parse_filter(part) {
+            Some(filter)    => filters.push(filter),
+            None            => return None,
+        };
+    }
+    if filters.len() > 0 { Some(filters) } else { None }
+}
+
+
+fn parse_filter(part: &str) -> Option<Attr> {
+    if !part.contains('=') {
+        return None;
+    }
+    let mut kv = part.splitn(2, '=').map(|s| s.trim());
+    Some(Attr::new(kv.next().unwrap_or(""), kv.next().unwrap_or("")))
+}
+
+
+fn has_attr(item: &TodoItem, key: &str, value: &str) -> bool {
+    // items without a status are to be done
+    if key == "status" && item.status.is_none() {
+        return value == "todo";
+    }
+    item.attrs.iter().any(|attr| {
+        attr.key == key && if key == TAGS_ATTR {
+            attr.value.split(',').any(|tag| tag.trim() == value)
+        } else {
+            attr.value.eq_ignore_ascii_case(value)
+        }
+    })
+}
+
+
+/// Find the ID of the item `selector` refers to: the ID itself, the file
+/// name of the item, its whole heading, a prefix of its stable ID or a part
+/// of its heading. When nothing else matches, the letters of the selector
+/// are looked for in the headings in order, so "wkrep" finds "Weekly
+/// report". More than one match is an error listing them.
+pub fn select_item(items: &Vec<Rc<TodoItem>>, selector: &str)
+                   -> TodoResult<i32> {
+    if let Ok(id) = selector.parse::<i32>() {
+        // stable IDs can start with digits only, so the number may be both
+        let mut matches: Vec<&Rc<TodoItem>> = items.iter().filter(|item| {
+            selector.len() >= MIN_ID_PREFIX &&
+                item.stable_id().starts_with(selector)
+        }).collect();
+        if matches.len() == 0 {
+            return Ok(id);
+        }
+        if let Some(item) = items.iter().find(|item| item.id == id) {
+            if !matches.iter().any(|m| m.id == id) {
+                matches.insert(0, item);
+            }
+        }
+        return one_match(selector, matches);
+    }
+
+    // a path to an item file selects the item by the name of the file
+    let path = Path::new(selector);
+    let name = if path.is_file() { path.file_name() } else { None };
+    let by_file = items.iter().find(|item| {
+        let file = Path::new(&item.filename);
+        item.filename == selector ||
+            file.file_name().map_or(false, |n| n == selector) ||
+            (name.is_some() && file.file_name() == name)
+    });
+    if let Some(item) = by_file {
+        return Ok(item.id);
+    }
+
+    let sel = selector.to_lowercase();
+    let whole: Vec<&Rc<TodoItem>> = items.iter().filter(|item| {
+        item.heading.to_lowercase() == sel.trim()
+    }).collect();
+    if whole.len() > 0 {
+        return one_match(selector, whole);
+    }
+
+    let is_id_prefix = sel.len() >= MIN_ID_PREFIX &&
+                       sel.chars().all(|c| c.is_digit(16));
+    let mut matches: Vec<&Rc<TodoItem>> = items.iter().filter(|item| {
+        (is_id_prefix && item.stable_id().starts_with(&sel)) ||
+            item.heading.to_lowercase().contains(&sel)
+    }).collect();
+    if matches.len() == 0 {
+        matches = items.iter().filter(|item| {
+            fuzzy_match(&item.heading.to_lowercase(), &sel)
+        }).collect();
+    }
+    one_match(selector, matches)
+}
+
+
+/// Get the ID of the only item in `matches`, or an error listing them.
+fn one_match(selector: &str, matches: Vec<&Rc<TodoItem>>) -> TodoResult<i32> {
+    match matches.len() {
+        0   => {
+            let err_msg = format!("No item matches '{}'", selector);
+            Err(TodoError::new(TodoErrorKind::Other, err_msg))
+        },
+        1   => Ok(matches[0].id),
+        n   => {
+            let mut err_msg = format!("'{}' matches {} items, select one:",
+                                      selector, n);
+            for item in matches {
+                err_msg.push_str(&format!("\n    [{:3}] {} ({}, {})", item.id,
+                                          item.heading, item.stable_id(),
+                                          item.filename));
+            }
+            Err(TodoError::new(TodoErrorKind::Other, err_msg))
+        },
+    }
+}
+
+
+/// Check whether the letters of `pattern`, other than whitespace, are in
+/// `text` in the same order.
+fn fuzzy_match(text: &str, pattern: &str) -> bool {
+    let mut chars = text.chars();
+    pattern.chars().filter(|c| !c.is_whitespace())
+                   .all(|p| chars.any(|c| c == p))
+}
+
+
 pub fn remove_item_by_id(items: &mut Vec<Rc<TodoItem>>, i: i32)
                         -> Option<Rc<TodoItem>> {
     items.iter().position(|ref p| p.id == i).map(|e| items.remove(e))
 }
+
+
+/// Load and parse the items with the given IDs and keys. The files are
+/// split between threads, the results are in the same order as `keys`.
+fn load_items<'a>(store: &dyn Store, keys: &[(i32, &'a str)], bodies: bool)
+                  -> Vec<(&'a str, TodoResult<TodoItem>)> {
+    let load = |&(id, key): &(i32, &'a str)| {
+        // stamped before reading, so a change while reading is not missed
+        let stamp = store.stamp(key);
+        let item = if bodies {
+            store.load(key).and_then(|c| TodoItem::new_from_str(key, &c, id))
+        } else {
+            // the steps of the checklist are counted, the body is not kept
+            let mut checklist = (0, 0);
+            store.load_header(key, &mut |line| {
+                todo_item::count_step(&mut checklist, line)
+            }).and_then(|h| TodoItem::new_from_header(key, &h, id))
+              .map(|mut item| { item.checklist = checklist; item })
+        };
+        (key, item.map(|mut item| { item.stamp = stamp; item }))
+    };
+
+    let threads = thread::available_parallelism().map(|n| n.get())
+                                                 .unwrap_or(1);
+    // starting threads is not worth it for a few files
+    let threads = cmp::min(threads, keys.len() / ITEMS_PER_THREAD);
+    if threads <= 1 {
+        return keys.iter().map(load).collect();
+    }
+
+    let chunk = (keys.len() + threads - 1) / threads;
+    thread::scope(|scope| {
+        let handles: Vec<_> = keys.chunks(chunk).map(|part| {
+            scope.spawn(move || part.iter().map(load).collect::<Vec<_>>())
+        }).collect();
+        handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+    })
+}
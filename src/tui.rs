@@ -0,0 +1,454 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::io::{self, Stdout, Write};
+use std::rc::Rc;
+
+use crossterm::{cursor, event, execute, queue, terminal};
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+
+use action;
+use attr::Attr;
+use color;
+use error::TodoResult;
+use item_file;
+use opt::Opt;
+use status::Status;
+use store::Store;
+use todo_item::TodoItem;
+use todo_items;
+use util;
+
+const HELP: &'static str =
+    "q quit  tab pane  / filter  space done  p postpone  x trash  e edit  \
+     r reload";
+const PANE_NAMES: [&'static str; 3] = ["Today", "Agenda", "All"];
+
+
+#[derive(Clone,Copy,PartialEq)]
+enum Mode {
+    ConfirmTrash,
+    Filter,
+    Normal,
+}
+
+
+#[derive(Clone,Copy,PartialEq)]
+enum Pane {
+    Today,
+    Agenda,
+    All,
+}
+
+
+struct Tui<'a> {
+    filter:     String,
+    items:      Vec<Rc<TodoItem>>,
+    message:    String,
+    mode:       Mode,
+    opt:        &'a Opt,
+    pane:       Pane,
+    scroll:     usize,
+    selected:   usize,
+    store:      &'a dyn Store,
+}
+
+
+/// Puts the terminal in raw mode on the alternate screen, and restores it
+/// when dropped so that errors and panics do not leave it in a bad state.
+struct Screen;
+
+
+impl Screen {
+    fn enter() -> io::Result<Screen> {
+        try!(terminal::enable_raw_mode());
+        try!(execute!(io::stdout(), terminal::EnterAlternateScreen,
+                      cursor::Hide));
+        Ok(Screen)
+    }
+}
+
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show,
+                         terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+
+/// Run the interactive view until the user quits.
+pub fn run(opt: &Opt, store: &dyn Store, items: Vec<Rc<TodoItem>>)
+           -> TodoResult<()> {
+    let mut tui = Tui {
+        filter:     String::new(),
+        items:      items,
+        message:    String::new(),
+        mode:       Mode::Normal,
+        opt:        opt,
+        pane:       Pane::Today,
+        scroll:     0,
+        selected:   0,
+        store:      store,
+    };
+
+    let mut screen = Some(try!(Screen::enter()));
+    let mut out = io::stdout();
+    loop {
+        try!(tui.draw(&mut out));
+
+        let key = match try!(event::read()) {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _   => continue,
+        };
+        tui.message.clear();
+
+        if tui.mode == Mode::Normal && (key.code == KeyCode::Char('e') ||
+                                        key.code == KeyCode::Enter) {
+            // the editor needs the terminal for itself
+            screen.take();
+            tui.edit_selected();
+            screen = Some(try!(Screen::enter()));
+            continue;
+        }
+
+        let ctrl_c = key.code == KeyCode::Char('c') &&
+                     key.modifiers.contains(KeyModifiers::CONTROL);
+        if ctrl_c || !tui.handle_key(key.code) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+
+impl<'a> Tui<'a> {
+    fn draw(&mut self, out: &mut Stdout) -> io::Result<()> {
+        let (w, h) = try!(terminal::size());
+        let (width, height) = (w as usize, h as usize);
+        let visible = self.visible_items();
+
+        // tabs, filter, two rules and the status line take five rows, the
+        // rest is shared between the list and the preview
+        let rows = height.saturating_sub(5);
+        let list_rows = if rows > 2 { rows * 3 / 5 } else { rows };
+        let preview_rows = rows - list_rows;
+
+        if self.selected >= visible.len() {
+            self.selected = visible.len().saturating_sub(1);
+        }
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if list_rows > 0 && self.selected >= self.scroll + list_rows {
+            self.scroll = self.selected + 1 - list_rows;
+        }
+
+        try!(queue!(out, terminal::Clear(terminal::ClearType::All)));
+
+        // pane tabs and today's date
+        try!(queue!(out, cursor::MoveTo(0, 0)));
+        for (i, name) in PANE_NAMES.iter().enumerate() {
+            let tab = format!(" {} ", name);
+            if i == self.pane as usize {
+                try!(queue!(out, SetAttribute(Attribute::Reverse), Print(tab),
+                            SetAttribute(Attribute::Reset)));
+            } else {
+                try!(queue!(out, Print(tab)));
+            }
+        }
+        let today_str = self.opt.clock.today_str().unwrap_or("".to_string());
+        try!(queue!(out, cursor::MoveTo(w.saturating_sub(11), 0),
+                    Print(&today_str)));
+
+        let filter_line = match self.mode {
+            Mode::Filter    => format!("Filter: {}_", self.filter),
+            _ if self.filter.len() > 0 => format!("Filter: {}", self.filter),
+            _               => "".to_string(),
+        };
+        try!(queue!(out, cursor::MoveTo(0, 1),
+                    Print(util::truncate(&filter_line, width))));
+
+        let rule: String = (0..width).map(|_| '-').collect();
+        try!(queue!(out, cursor::MoveTo(0, 2), Print(&rule)));
+
+        for row in 0..list_rows {
+            let i = self.scroll + row;
+            let item = match visible.get(i) {
+                Some(item)  => item,
+                None        => break,
+            };
+            let date = item.get_date_str().unwrap_or("".to_string());
+            let line = util::pad(&format!("{:10} [{:3}] {}", date, item.id,
+                                          item.heading), width);
+            try!(queue!(out, cursor::MoveTo(0, (3 + row) as u16)));
+            if i == self.selected {
+                try!(queue!(out, SetAttribute(Attribute::Reverse), Print(line),
+                            SetAttribute(Attribute::Reset)));
+            } else {
+                let style = action::item_style(self.opt, item, &today_str);
+                try!(queue!(out, Print(color::paint(self.opt, &style, &line))));
+            }
+        }
+        if visible.len() == 0 {
+            try!(queue!(out, cursor::MoveTo(0, 3), Print("No items")));
+        }
+
+        let rule_row = (3 + list_rows) as u16;
+        try!(queue!(out, cursor::MoveTo(0, rule_row), Print(&rule)));
+
+        if let Some(item) = visible.get(self.selected) {
+            let mut lines: Vec<String> = vec![item.heading.clone()];
+            for attr in item.attrs.iter() {
+                lines.push(format!("{}: {}", attr.key, attr.value));
+            }
+            lines.push("".to_string());
+            if let Some(ref body) = item.body {
+                lines.extend(body.lines().map(|l| l.to_string()));
+            }
+
+            for (row, line) in lines.iter().take(preview_rows).enumerate() {
+                try!(queue!(out, cursor::MoveTo(0, rule_row + 1 + row as u16),
+                            Print(util::truncate(line, width))));
+            }
+        }
+
+        let status = match self.mode {
+            Mode::ConfirmTrash  => "Move item to trash? (y/n)".to_string(),
+            _ if self.message.len() > 0 => self.message.clone(),
+            _                   => HELP.to_string(),
+        };
+        try!(queue!(out, cursor::MoveTo(0, h.saturating_sub(1)),
+                    Print(util::truncate(&status, width))));
+
+        out.flush()
+    }
+
+
+    fn edit_selected(&mut self) {
+        let item = match self.selected_item() {
+            Some(item)  => item,
+            None        => return,
+        };
+        if let Err(e) = action::edit_item(self.opt, self.store, &self.items,
+                                            item.id) {
+            self.message = format!("Error editing item: {}", e);
+        }
+        self.reload(Some(&item.filename));
+    }
+
+
+    /// Handle a key press, returning false when the user wants to quit.
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        match self.mode {
+            Mode::ConfirmTrash  => {
+                self.mode = Mode::Normal;
+                if code == KeyCode::Char('y') {
+                    self.trash_selected();
+                }
+            },
+            Mode::Filter        => {
+                match code {
+                    KeyCode::Char(c)    => self.filter.push(c),
+                    KeyCode::Backspace  => { self.filter.pop(); },
+                    KeyCode::Enter      => self.mode = Mode::Normal,
+                    KeyCode::Esc        => {
+                        self.filter.clear();
+                        self.mode = Mode::Normal;
+                    },
+                    _                   => {},
+                };
+                self.selected = 0;
+            },
+            Mode::Normal        => {
+                match code {
+                    KeyCode::Char('q') | KeyCode::Esc   => return false,
+                    KeyCode::Tab | KeyCode::Right       => self.switch_pane(1),
+                    KeyCode::BackTab | KeyCode::Left    => self.switch_pane(2),
+                    KeyCode::Char('1')  => self.set_pane(Pane::Today),
+                    KeyCode::Char('2')  => self.set_pane(Pane::Agenda),
+                    KeyCode::Char('3')  => self.set_pane(Pane::All),
+                    KeyCode::Char('j') | KeyCode::Down  => self.selected += 1,
+                    KeyCode::Char('k') | KeyCode::Up    => {
+                        self.selected = self.selected.saturating_sub(1);
+                    },
+                    KeyCode::Char('g') | KeyCode::Home  => self.selected = 0,
+                    KeyCode::Char('G') | KeyCode::End   => {
+                        self.selected = self.visible_items().len();
+                    },
+                    KeyCode::Char('/')  => self.mode = Mode::Filter,
+                    KeyCode::Char(' ') | KeyCode::Char('d') => {
+                        self.toggle_done();
+                    },
+                    KeyCode::Char('p')  => self.postpone_selected(),
+                    KeyCode::Char('x') | KeyCode::Delete => {
+                        if self.selected_item().is_some() {
+                            self.mode = Mode::ConfirmTrash;
+                        }
+                    },
+                    KeyCode::Char('r')  => {
+                        let selected = self.selected_item();
+                        self.reload(selected.as_ref().map(|i| &i.filename[..]));
+                    },
+                    _                   => {},
+                };
+            },
+        };
+        true
+    }
+
+
+    /// Get the items of the current pane.
+    fn pane_items(&self) -> Vec<Rc<TodoItem>> {
+        let today_str = self.opt.clock.today_str().unwrap_or("".to_string());
+        match self.pane {
+            Pane::Today     => {
+                let undone = todo_items::get_undone_items(&self.items);
+                let mut list = todo_items::get_items_on_date(&undone,
+                                                             &today_str);
+                list.extend(todo_items::get_items_before(&undone, &today_str));
+                list.extend(todo_items::get_dateless_items(&undone));
+                list
+            },
+            Pane::Agenda    => {
+                action::agenda_items(self.opt, &self.items)
+                    .map(|(items, _)| items)
+                    .unwrap_or(Vec::new())
+            },
+            Pane::All       => self.items.clone(),
+        }
+    }
+
+
+    fn postpone(&self, item: &Rc<TodoItem>) -> TodoResult<String> {
+        let store = self.store;
+        let date = try!(action::postpone_date(self.opt, item));
+        let date_str = try!(util::date_to_str(&date));
+        let summary = format!("Postpone '{}' to {}", item.heading, date_str);
+        try!(action::change_items(self.opt, store, "postpone", &summary,
+                                  &[item.clone()], |item| {
+            action::reschedule_item(store, item, &date_str)
+        }));
+        Ok(date_str)
+    }
+
+
+    fn postpone_selected(&mut self) {
+        let item = match self.selected_item() {
+            Some(item)  => item,
+            None        => return,
+        };
+        self.message = match self.postpone(&item) {
+            Ok(date_str)    => format!("Postponed to {}", date_str),
+            Err(e)          => format!("Error postponing item: {}", e),
+        };
+        self.reload(Some(&item.filename));
+    }
+
+
+    /// Reload all items, keeping the item in `filename` selected if it is
+    /// still visible.
+    fn reload(&mut self, filename: Option<&str>) {
+        match todo_items::get_todo_items(self.store) {
+            Ok(items)   => self.items = items,
+            Err(e)      => {
+                self.message = format!("Could not parse todo items: {}", e);
+                return;
+            },
+        };
+        if let Some(filename) = filename {
+            if let Some(i) = self.visible_items().iter()
+                                 .position(|item| item.filename == filename) {
+                self.selected = i;
+            }
+        }
+    }
+
+
+    fn selected_item(&self) -> Option<Rc<TodoItem>> {
+        self.visible_items().get(self.selected).cloned()
+    }
+
+
+    fn set_pane(&mut self, pane: Pane) {
+        self.pane = pane;
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+
+    fn switch_pane(&mut self, step: usize) {
+        let pane = match (self.pane as usize + step) % 3 {
+            0   => Pane::Today,
+            1   => Pane::Agenda,
+            _   => Pane::All,
+        };
+        self.set_pane(pane);
+    }
+
+
+    fn toggle_done(&mut self) {
+        let item = match self.selected_item() {
+            Some(item)  => item,
+            None        => return,
+        };
+        let status = if item.status == Some(Status::Done) { "todo" }
+                     else { "done" };
+        let (done, total) = todo_items::get_progress(&self.items, &item);
+        if status == "done" && done < total {
+            self.message = format!("[{}] has {} open subtasks, not marked \
+                                    done", item.id, total - done);
+            return;
+        }
+        let store = self.store;
+        let attrs = [Attr::new("status", status)];
+        let summary = format!("Mark '{}' {}", item.heading, status);
+        let marked = action::change_items(self.opt, store, status, &summary,
+                                          &[item.clone()], |item| {
+            item_file::set_attrs(store, item, &attrs)
+        });
+        self.message = match marked {
+            Ok(())  => format!("Marked [{}] {}", item.id, status),
+            Err(e)  => format!("Error setting status: {}", e),
+        };
+        self.reload(Some(&item.filename));
+    }
+
+
+    fn trash_selected(&mut self) {
+        let item = match self.selected_item() {
+            Some(item)  => item,
+            None        => return,
+        };
+        let store = self.store;
+        let summary = format!("Trash '{}'", item.heading);
+        let mut moved_to = None;
+        let trashed = action::change_items(self.opt, store, "delete", &summary,
+                                           &[item.clone()], |item| {
+            try!(item_file::check_unchanged(store, item));
+            moved_to = try!(store.trash(&item.filename));
+            Ok(())
+        });
+        self.message = match trashed.map(|_| moved_to) {
+            Ok(Some(key))   => format!("Moved to {}", key),
+            Ok(None)        => format!("Deleted [{}]", item.id),
+            Err(e)          => format!("Error trashing item: {}", e),
+        };
+        self.reload(None);
+    }
+
+
+    /// Get the items of the current pane that match the filter. The filter
+    /// is a case insensitive substring of the heading or the body.
+    fn visible_items(&self) -> Vec<Rc<TodoItem>> {
+        let filter = self.filter.to_lowercase();
+        self.pane_items().into_iter().filter(|item| {
+            filter.len() == 0 ||
+                item.heading.to_lowercase().contains(&filter) ||
+                item.body.as_ref().map_or(false, |body| {
+                    body.to_lowercase().contains(&filter)
+                })
+        }).collect()
+    }
+}
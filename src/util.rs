@@ -1,8 +1,15 @@
 // Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
 // Licensed under the 2-clause BSD license, see LICENSE for details.
 
+use std::env;
+use std::io::{self, IsTerminal, Write};
+
+use terminal_size::{terminal_size, Width};
 use time;
-use time::Tm;
+use time::{Duration, Tm};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const DEFAULT_TERMINAL_WIDTH: usize = 80;
 
 macro_rules! print_err {
     ($($arg:tt)*) => (
@@ -20,7 +27,182 @@ macro_rules! print_err {
     )
 }
 
+macro_rules! try_opt(
+    ($e:expr) => (match $e { Some(e) => e, None => return None })
+);
+
 
 pub fn date_to_str(date: &Tm) -> Result<String, time::ParseError> {
     time::strftime("%Y-%m-%d", &date)
 }
+
+
+/// 64-bit FNV-1a.
+pub fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in data {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+
+/// Return a date `days` days from `date`. The returned Tm is in UTC and has
+/// its weekday and yearday fields filled in.
+pub fn add_days(date: &Tm, days: i64) -> Tm {
+    *date + Duration::days(days)
+}
+
+
+pub fn str_to_date(date_str: &str) -> Result<Tm, time::ParseError> {
+    time::strptime(date_str, "%Y-%m-%d")
+}
+
+
+/// Parse either an absolute date (YYYY-MM-DD) or a relative offset of days
+/// from `today` (+N or -N).
+pub fn parse_date_arg(arg: &str, today: &Tm) -> Result<Tm, String> {
+    let arg = arg.trim();
+    if arg.starts_with('+') || arg.starts_with('-') {
+        match arg[1..].parse::<i64>() {
+            Ok(n)   => {
+                let days = if arg.starts_with('-') { -n } else { n };
+                Ok(add_days(today, days))
+            },
+            Err(err) => Err(format!("invalid day offset '{}': {}", arg, err)),
+        }
+    } else {
+        match str_to_date(arg) {
+            Ok(date)    => Ok(add_days(&date, 0)),
+            Err(err)    => Err(format!("invalid date '{}': {}", arg, err)),
+        }
+    }
+}
+
+
+/// Return `date` if it is a workday, otherwise the next Monday.
+pub fn next_workday(date: &Tm) -> Tm {
+    let date = add_days(date, 0);
+    match date.tm_wday {
+        0   => add_days(&date, 1),
+        6   => add_days(&date, 2),
+        _   => date,
+    }
+}
+
+
+
+/// Get the terminal width from $COLUMNS or from the terminal itself,
+/// defaulting to 80 columns.
+pub fn terminal_width() -> usize {
+    output_width().unwrap_or(DEFAULT_TERMINAL_WIDTH)
+}
+
+
+/// Get the width output should be fitted to, or None if stdout is not a
+/// terminal and $COLUMNS is not set, in which case lines are not truncated.
+pub fn output_width() -> Option<usize> {
+    let columns = env::var("COLUMNS").ok()
+                                     .and_then(|c| c.parse::<usize>().ok())
+                                     .filter(|&c| c > 0);
+    if columns.is_some() {
+        return columns;
+    }
+
+    if io::stdout().is_terminal() {
+        if let Some((Width(w), _)) = terminal_size() {
+            return Some(w as usize);
+        }
+    }
+    None
+}
+
+
+/// Ask `question` and read the answer, trimmed and in lower case. Returns
+/// None when there is nothing to read the answer from.
+pub fn ask(question: &str) -> io::Result<Option<String>> {
+    print!("{} ", question);
+    try!(io::stdout().flush());
+    let mut answer = String::new();
+    if try!(io::stdin().read_line(&mut answer)) == 0 {
+        println!("");
+        return Ok(None);
+    }
+    Ok(Some(answer.trim().to_lowercase()))
+}
+
+
+/// Pad `s` with spaces to `width` terminal columns, truncating it if it is
+/// wider than that.
+pub fn pad(s: &str, width: usize) -> String {
+    let mut padded = truncate(s, width);
+    for _ in padded.width()..width {
+        padded.push(' ');
+    }
+    padded
+}
+
+
+/// Truncate `s` to fit in `width` terminal columns, marking the cut with an
+/// ellipsis. Double width characters are taken into account.
+pub fn truncate(s: &str, width: usize) -> String {
+    if s.width() <= width {
+        return s.to_string();
+    }
+
+    let mut truncated = String::new();
+    let mut used = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        // leave room for the ellipsis
+        if used + w + 1 > width {
+            break;
+        }
+        truncated.push(c);
+        used += w;
+    }
+    if width > 0 {
+        truncated.push('…');
+    }
+    truncated
+}
+
+
+/// Split a command line like "code --wait" into words. Words can be quoted
+/// with ' or " and single characters escaped with a backslash.
+pub fn split_command(cmd: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = cmd.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', q) if q != Some('\'') => {
+                if let Some(c) = chars.next() {
+                    word.get_or_insert(String::new()).push(c);
+                }
+            },
+            (c, Some(q)) if c == q  => { quote = None; },
+            (c, Some(_))            => {
+                word.get_or_insert(String::new()).push(c);
+            },
+            ('\'', None) | ('"', None) => {
+                quote = Some(c);
+                word.get_or_insert(String::new());
+            },
+            (c, None) if c.is_whitespace()  => {
+                if let Some(w) = word.take() {
+                    words.push(w);
+                }
+            },
+            (c, None)               => {
+                word.get_or_insert(String::new()).push(c);
+            },
+        };
+    }
+    if let Some(w) = word {
+        words.push(w);
+    }
+    words
+}
@@ -0,0 +1,392 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crossterm::{cursor, execute, terminal};
+use time;
+
+use action::{self, Action};
+use error::{TodoError, TodoErrorKind, TodoResult};
+use opt::Opt;
+use store::Store;
+use todo_item::TodoItem;
+use todo_items;
+
+// how often the todo dir is scanned when inotify is not available
+const POLL_INTERVAL_MS: u64 = 1000;
+// time to wait for more events after a change, editors often write a file
+// in several steps
+const SETTLE_MS: u64 = 100;
+
+
+/// What happened while waiting on the store.
+pub enum Change {
+    /// The items with these keys were created, changed or removed.
+    Items(Vec<String>),
+    /// Changes were lost, everything has to be reloaded.
+    Rescan,
+    Timeout,
+}
+
+
+/// Reports changes to the items of a store.
+pub trait Watch {
+    /// Wait until items change or `timeout` passes.
+    fn wait(&mut self, timeout: Duration) -> io::Result<Change>;
+}
+
+
+/// Watches the files in a directory, with inotify where it is available and
+/// by polling file metadata elsewhere.
+pub struct Watcher {
+    dir:        PathBuf,
+    #[cfg(target_os = "linux")]
+    inotify:    Option<inotify::Inotify>,
+    snapshot:   HashMap<PathBuf, (Option<SystemTime>, u64)>,
+}
+
+
+impl Watcher {
+    pub fn new(dir: &Path) -> Watcher {
+        let mut watcher = Watcher {
+            dir:        dir.to_path_buf(),
+            #[cfg(target_os = "linux")]
+            inotify:    inotify::Inotify::new(dir).ok(),
+            snapshot:   HashMap::new(),
+        };
+        if !watcher.has_inotify() {
+            watcher.snapshot = snapshot(dir);
+        }
+        watcher
+    }
+
+
+    #[cfg(target_os = "linux")]
+    fn has_inotify(&self) -> bool {
+        self.inotify.is_some()
+    }
+
+
+    #[cfg(not(target_os = "linux"))]
+    fn has_inotify(&self) -> bool {
+        false
+    }
+
+
+    fn poll(&mut self, timeout: Duration) -> io::Result<Change> {
+        let start = Instant::now();
+        loop {
+            let current = snapshot(&self.dir);
+            let mut changed: Vec<PathBuf> = Vec::new();
+            for (path, meta) in current.iter() {
+                if self.snapshot.get(path) != Some(meta) {
+                    changed.push(path.clone());
+                }
+            }
+            for path in self.snapshot.keys() {
+                if !current.contains_key(path) {
+                    changed.push(path.clone());
+                }
+            }
+            self.snapshot = current;
+
+            if changed.len() > 0 {
+                return Ok(Change::Items(to_keys(changed)));
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Ok(Change::Timeout);
+            }
+            let interval = Duration::from_millis(POLL_INTERVAL_MS);
+            thread::sleep(if timeout - elapsed < interval { timeout - elapsed }
+                          else { interval });
+        }
+    }
+}
+
+
+impl Watch for Watcher {
+    fn wait(&mut self, timeout: Duration) -> io::Result<Change> {
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(ref mut inotify) = self.inotify {
+                return inotify.wait(&self.dir, timeout);
+            }
+        }
+        self.poll(timeout)
+    }
+}
+
+
+fn is_hidden(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+
+/// Turn paths into DirStore keys, the names of the files.
+fn to_keys(paths: Vec<PathBuf>) -> Vec<String> {
+    paths.into_iter().filter_map(|p| {
+        p.file_name().map(|name| name.to_string_lossy().into_owned())
+    }).collect()
+}
+
+
+/// Get the modification time and size of the visible files in `dir`.
+fn snapshot(dir: &Path) -> HashMap<PathBuf, (Option<SystemTime>, u64)> {
+    let mut files = HashMap::new();
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let hidden = entry.file_name().to_str().map(is_hidden)
+                                                   .unwrap_or(false);
+            if hidden {
+                continue;
+            }
+            if let Ok(meta) = entry.metadata() {
+                if meta.is_file() {
+                    files.insert(entry.path(),
+                                 (meta.modified().ok(), meta.len()));
+                }
+            }
+        }
+    }
+    files
+}
+
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::ffi::{CString, OsStr};
+    use std::io;
+    use std::mem;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+
+    use libc;
+
+    use super::{Change, SETTLE_MS, is_hidden, to_keys};
+
+    pub struct Inotify {
+        fd:     libc::c_int,
+    }
+
+
+    impl Inotify {
+        pub fn new(dir: &Path) -> io::Result<Inotify> {
+            let path = match CString::new(dir.as_os_str().to_os_string()
+                                             .into_vec()) {
+                Ok(path)    => path,
+                Err(e)      => {
+                    return Err(io::Error::new(io::ErrorKind::Other, e));
+                },
+            };
+
+            let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let inotify = Inotify { fd: fd };
+
+            let mask = libc::IN_CLOSE_WRITE | libc::IN_CREATE |
+                       libc::IN_DELETE | libc::IN_MODIFY |
+                       libc::IN_MOVED_FROM | libc::IN_MOVED_TO;
+            let wd = unsafe {
+                libc::inotify_add_watch(fd, path.as_ptr(), mask)
+            };
+            if wd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(inotify)
+        }
+
+
+        /// Wait for events up to `timeout`. Once something happens, events
+        /// are collected for a short while longer.
+        pub fn wait(&mut self, dir: &Path, timeout: Duration)
+                    -> io::Result<Change> {
+            let start = Instant::now();
+            let mut changed: Vec<PathBuf> = Vec::new();
+            let mut overflow = false;
+
+            loop {
+                let left = if changed.len() > 0 || overflow {
+                    Duration::from_millis(SETTLE_MS)
+                } else if start.elapsed() < timeout {
+                    timeout - start.elapsed()
+                } else {
+                    Duration::from_millis(0)
+                };
+
+                if !try!(self.poll(left)) {
+                    break;
+                }
+                overflow |= try!(self.read_events(dir, &mut changed));
+            }
+
+            if overflow {
+                Ok(Change::Rescan)
+            } else if changed.len() > 0 {
+                Ok(Change::Items(to_keys(changed)))
+            } else {
+                Ok(Change::Timeout)
+            }
+        }
+
+
+        /// Return true if there are events to read within `timeout`.
+        fn poll(&self, timeout: Duration) -> io::Result<bool> {
+            let mut pfd = libc::pollfd {
+                fd:         self.fd,
+                events:     libc::POLLIN,
+                revents:    0,
+            };
+            let ms = timeout.as_secs() * 1000 +
+                     timeout.subsec_nanos() as u64 / 1_000_000;
+            let ms = if ms > libc::c_int::max_value() as u64 {
+                libc::c_int::max_value()
+            } else {
+                ms as libc::c_int
+            };
+
+            loop {
+                let ret = unsafe { libc::poll(&mut pfd, 1, ms) };
+                if ret >= 0 {
+                    return Ok(ret > 0);
+                }
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+        }
+
+
+        /// Read pending events, adding the changed files to `changed`.
+        /// Returns true if the event queue overflowed.
+        fn read_events(&self, dir: &Path, changed: &mut Vec<PathBuf>)
+                       -> io::Result<bool> {
+            let mut buf = [0u8; 4096];
+            let len = unsafe {
+                libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void,
+                           buf.len())
+            };
+            if len < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let header = mem::size_of::<libc::inotify_event>();
+            let mut overflow = false;
+            let mut pos = 0;
+            while pos + header <= len as usize {
+                let event: libc::inotify_event = unsafe {
+                    (buf[pos..].as_ptr() as *const libc::inotify_event)
+                        .read_unaligned()
+                };
+                let name_bytes = &buf[pos + header..
+                                      pos + header + event.len as usize];
+                pos += header + event.len as usize;
+
+                if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                    overflow = true;
+                    continue;
+                }
+
+                // the name is padded with NUL bytes
+                let end = name_bytes.iter().position(|&b| b == 0)
+                                           .unwrap_or(name_bytes.len());
+                let name = OsStr::from_bytes(&name_bytes[..end]);
+                let hidden = name.to_str().map(is_hidden).unwrap_or(false);
+                if end == 0 || hidden {
+                    continue;
+                }
+
+                let path = dir.join(name);
+                if !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+            Ok(overflow)
+        }
+    }
+
+
+    impl Drop for Inotify {
+        fn drop(&mut self) {
+            unsafe { libc::close(self.fd); }
+        }
+    }
+}
+
+
+/// Time left until the local date changes.
+fn until_midnight() -> Duration {
+    let now = time::now();
+    let secs = now.tm_hour * 3600 + now.tm_min * 60 + now.tm_sec;
+    // a second extra so that the date has surely changed
+    Duration::from_secs((24 * 3600 - secs) as u64 + 1)
+}
+
+
+fn render(opt: &Opt, view: &Action, items: &Vec<Rc<TodoItem>>)
+          -> TodoResult<()> {
+    try!(execute!(io::stdout(), terminal::Clear(terminal::ClearType::All),
+                  cursor::MoveTo(0, 0)));
+    match *view {
+        Action::Agenda      => action::agenda(opt, items),
+        Action::Month       => action::month(opt, items),
+        Action::Today       => { action::print_today(opt, items); Ok(()) },
+        Action::TodayOnly   => { action::print_today_only(opt, items); Ok(()) },
+        Action::Week        => action::week(opt, items),
+        _                   => Ok(()),
+    }
+}
+
+
+/// Show `view` and show it again whenever the todo items change or the date
+/// changes at midnight. Only the changed items are read again.
+pub fn watch(opt: &Opt, store: &dyn Store, view: &Action,
+             items: Vec<Rc<TodoItem>>) -> TodoResult<()> {
+    match *view {
+        Action::Agenda | Action::Month | Action::Today | Action::TodayOnly |
+        Action::Week    => {},
+        _               => {
+            return Err(TodoError::new(TodoErrorKind::Other,
+                                      "'--watch' only works with the agenda, \
+                                      today and calendar views".to_string()));
+        },
+    };
+
+    let mut watcher = try!(store.watch());
+    let mut items = items;
+    loop {
+        if let Err(e) = render(opt, view, &items) {
+            print_err!("Error: {}", e);
+        }
+
+        // with a fixed date there is no midnight to wait for
+        let timeout = if opt.clock.is_fixed() {
+            Duration::from_secs(24 * 3600)
+        } else {
+            until_midnight()
+        };
+
+        let changed = match try!(watcher.wait(timeout)) {
+            Change::Items(keys)     => keys,
+            Change::Rescan          => {
+                items = try!(todo_items::get_todo_headers(store));
+                continue;
+            },
+            Change::Timeout         => { continue; },
+        };
+        items = try!(todo_items::reload_todo_items(store, &items, &changed,
+                                                   false));
+    }
+}
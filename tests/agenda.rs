@@ -0,0 +1,79 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use common::TestDir;
+
+
+const ITEMS: &[(&str, &str)] = &[
+    ("last-week", "Last week's task\ndate: 2016-02-15\n"),
+    ("last-done", "Finished task\ndate: 2016-02-16\nstatus: done\n"),
+    ("today",     "Today's task\ndate: 2016-02-20\n"),
+    ("last-day",  "Last agenda day\ndate: 2016-02-27\n"),
+    ("too-late",  "Out of range\ndate: 2016-02-28\n"),
+];
+
+
+#[test]
+fn default_range_includes_today_and_last_day() {
+    let dir = TestDir::with_items("default_range_includes_today_and_last_day",
+                                  ITEMS);
+    let out = dir.run_ok(&["-a", "--date", "2016-02-20"]);
+
+    assert!(out.starts_with("Agenda for the next 8 days, \
+                             2016-02-20 to 2016-02-27:\n"));
+    assert!(out.contains("Today's task"));
+    assert!(out.contains("Last agenda day"));
+    assert!(!out.contains("Out of range"));
+    assert!(!out.contains("Last week's task"));
+}
+
+
+#[test]
+fn past_days_include_done_items() {
+    let dir = TestDir::with_items("past_days_include_done_items", ITEMS);
+    let out = dir.run_ok(&["--past", "7", "--date", "2016-02-20"]);
+
+    assert!(out.starts_with("Agenda for the past 7 days, \
+                             2016-02-13 to 2016-02-19:\n"));
+    assert!(out.contains("Last week's task"));
+    assert!(out.contains("Finished task (done)"));
+    assert!(!out.contains("Today's task"));
+}
+
+
+#[test]
+fn explicit_range_is_inclusive() {
+    let dir = TestDir::with_items("explicit_range_is_inclusive", ITEMS);
+    let out = dir.run_ok(&["--from", "2016-02-15", "--to", "2016-02-20",
+                           "--date", "2016-02-20"]);
+
+    assert!(out.starts_with("Agenda for 2016-02-15 to 2016-02-20:\n"));
+    assert!(out.contains("Last week's task"));
+    assert!(out.contains("Today's task"));
+    assert!(!out.contains("Last agenda day"));
+}
+
+
+#[test]
+fn end_date_alone_shows_agenda_from_today() {
+    let dir = TestDir::with_items("end_date_alone_shows_agenda_from_today",
+                                  ITEMS);
+    let out = dir.run_ok(&["--to", "2016-02-27", "--date", "2016-02-20"]);
+
+    assert!(out.starts_with("Agenda for 2016-02-20 to 2016-02-27:\n"));
+    assert!(out.contains("Last agenda day"));
+    assert!(!out.contains("Out of range"));
+}
+
+
+#[test]
+fn negative_agenda_days_is_an_error() {
+    let dir = TestDir::with_items("negative_agenda_days_is_an_error", ITEMS);
+    let out = dir.run(&["-A", "-3"], &[]);
+
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr)
+            .contains("must be at least 1, see '--past'"));
+}
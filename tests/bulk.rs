@@ -0,0 +1,91 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use common::TestDir;
+
+
+const ITEMS: &[(&str, &str)] = &[
+    ("milk", "Buy milk\ndate: 2016-03-01\n"),
+    ("bob",  "Call Bob\ndate: 2016-03-02\nstatus: done\n"),
+    ("bike", "Fix bike\ndate: 2016-03-03\n\nFlat tyre.\n"),
+];
+
+
+fn stable_id(dir: &TestDir, file: &str) -> String {
+    let out = dir.run_ok(&["-s", file]);
+    let line = out.lines().find(|l| l.starts_with("stable id: ")).unwrap();
+    line["stable id: ".len()..].to_string()
+}
+
+
+#[test]
+fn bulk_edit_changes_and_adds_items() {
+    let dir = TestDir::with_items("bulk_edit_changes_and_adds_items", ITEMS);
+    let document = format!("{} 2016-03-01 todo Buy oat milk\n\
+                            {} 2016-03-02 done Call Bob\n\
+                            {} - done Fix bike\n\
+                            2016-03-05 Water plants\n",
+                           stable_id(&dir, "milk"), stable_id(&dir, "bob"),
+                           stable_id(&dir, "bike"));
+    let editor = dir.editor(&document);
+    dir.run_env_ok(&["--bulk-edit", "milk,bob,bike"], &[("EDITOR", &editor)]);
+
+    assert_eq!(dir.read_item("milk"), "Buy oat milk\ndate: 2016-03-01\n");
+    assert_eq!(dir.read_item("bob"),
+               "Call Bob\ndate: 2016-03-02\nstatus: done\n");
+    assert_eq!(dir.read_item("bike"),
+               "Fix bike\nstatus: done\n\nFlat tyre.\n");
+    assert_eq!(dir.read_item("water-plants"),
+               "Water plants\ndate: 2016-03-05\n");
+
+    // all of it is one change
+    dir.run_ok(&["--undo"]);
+    assert_eq!(dir.read_item("milk"), "Buy milk\ndate: 2016-03-01\n");
+    assert!(!dir.todo_dir.join("water-plants").exists());
+}
+
+
+#[test]
+fn removed_lines_are_trashed_when_confirmed() {
+    let dir = TestDir::with_items("removed_lines_are_trashed_when_confirmed",
+                                  ITEMS);
+    let document = format!("{} 2016-03-01 todo Buy milk\n",
+                           stable_id(&dir, "milk"));
+    let editor = dir.editor(&document);
+
+    let out = dir.run_input(&["--bulk-edit", "status=todo"],
+                            &[("EDITOR", &editor)], "n\n");
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Fix bike"));
+    assert!(dir.todo_dir.join("bike").exists());
+
+    let out = dir.run_input(&["--bulk-edit", "status=todo"],
+                            &[("EDITOR", &editor)], "y\n");
+    assert!(out.status.success());
+    assert!(!dir.todo_dir.join("bike").exists());
+    assert!(dir.todo_dir.join(".trash").join("bike").exists());
+    assert!(dir.todo_dir.join("bob").exists());
+
+    // what is already in the trash is kept
+    dir.add_item("bike", "Fix bike again\n");
+    dir.run_input(&["--bulk-edit", "status=todo"], &[("EDITOR", &editor)],
+                  "y\n");
+    assert_eq!(dir.read_item(".trash/bike"),
+               "Fix bike\ndate: 2016-03-03\n\nFlat tyre.\n");
+    assert_eq!(dir.read_item(".trash/bike.1"), "Fix bike again\n");
+}
+
+
+#[test]
+fn bad_lines_leave_items_unchanged() {
+    let dir = TestDir::with_items("bad_lines_leave_items_unchanged", ITEMS);
+    let document = format!("{} 2016-03-01 Buy milk\n", stable_id(&dir, "milk"));
+    let editor = dir.editor(&document);
+
+    let out = dir.run_input(&["--bulk-edit", "milk"], &[("EDITOR", &editor)],
+                            "a\n");
+    assert!(String::from_utf8_lossy(&out.stderr)
+                   .contains("expected ID, date, status and heading"));
+    assert_eq!(dir.read_item("milk"), "Buy milk\ndate: 2016-03-01\n");
+}
@@ -0,0 +1,53 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use common::TestDir;
+
+
+const ITEMS: &[(&str, &str)] = &[
+    ("overdue", "Overdue\ndate: 2016-02-03\n"),
+    ("meeting", "Meeting\ndate: 2016-02-20\n"),
+    ("dentist", "Dentist\ndate: 2016-02-20\n"),
+];
+
+
+#[test]
+fn week_has_iso_week_and_columns() {
+    let dir = TestDir::with_items("week_has_iso_week_and_columns", ITEMS);
+    let out = dir.run_env_ok(&["-w", "--date", "2016-02-19"],
+                             &[("COLUMNS", "80")]);
+
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "Week 07, 2016-02-15 to 2016-02-21:");
+    assert!(lines[1].starts_with("Mon 15    |Tue 16"));
+    assert!(lines[1].contains("Fri 19 <"));
+    assert!(out.contains("] Meeti…"));
+    assert!(!out.contains("Overdue"));
+}
+
+
+#[test]
+fn narrow_week_falls_back_to_list() {
+    let dir = TestDir::with_items("narrow_week_falls_back_to_list", ITEMS);
+    let out = dir.run_env_ok(&["-w", "--date", "2016-02-19"],
+                             &[("COLUMNS", "40")]);
+
+    assert!(out.contains("Fri 2016-02-19 (today):\nSat 2016-02-20:\n"));
+    assert!(out.contains("Meeting"));
+}
+
+
+#[test]
+fn month_marks_counts_and_overdue_days() {
+    let dir = TestDir::with_items("month_marks_counts_and_overdue_days",
+                                  ITEMS);
+    let out = dir.run_env_ok(&["-m", "--date", "2016-02-19"],
+                             &[("COLUMNS", "80")]);
+
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "February 2016");
+    assert!(lines[2].starts_with("05   1       2       3(1)!"));
+    assert!(lines[4].contains("19<     20(2)"));
+}
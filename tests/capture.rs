@@ -0,0 +1,84 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use std::fs;
+use std::thread;
+
+use common::TestDir;
+
+const MAIL: &str = "\
+From: Alice <alice@example.com>\r
+Subject: =?UTF-8?Q?Caf=C3=A9?= order\r
+Date: Tue, 1 Mar 2016 10:00:00 +0200\r
+Message-ID: <123@example.com>\r
+Content-Type: multipart/alternative; boundary=\"XYZ\"\r
+\r
+--XYZ\r
+Content-Type: text/plain; charset=utf-8\r
+Content-Transfer-Encoding: quoted-printable\r
+\r
+Three caf=C3=A9 machines, please, =\r
+by Friday.\r
+--XYZ\r
+Content-Type: text/html\r
+\r
+<p>Three machines</p>\r
+--XYZ--\r
+";
+
+
+#[test]
+fn capture_uses_first_line_as_heading() {
+    let dir = TestDir::new("capture_uses_first_line_as_heading");
+    let out = dir.run_input(&["--capture", "--date", "2016-02-05"], &[],
+                            "\nCheck backups\nThe nightly job failed.\n");
+    assert!(out.status.success());
+    assert_eq!(dir.read_item("check-backups"),
+               "Check backups\ndate: 2016-02-05\nsource: stdin\n\n\
+                The nightly job failed.\n");
+}
+
+
+#[test]
+fn capture_mail_uses_subject_and_date() {
+    let dir = TestDir::new("capture_mail_uses_subject_and_date");
+    let out = dir.run_input(&["--capture-mail"], &[], MAIL);
+    assert!(out.status.success());
+    assert_eq!(dir.read_item("café-order"),
+               "Café order\ndate: 2016-03-01\nsource: mail <123@example.com>\n\n\
+                Three café machines, please, by Friday.\n");
+
+    // --date overrides the date of the message
+    dir.run_input(&["--capture-mail", "--date", "2016-04-01"], &[], MAIL);
+    assert!(dir.read_item("café-order-2").contains("date: 2016-04-01\n"));
+}
+
+
+#[test]
+fn empty_capture_is_an_error() {
+    let dir = TestDir::new("empty_capture_is_an_error");
+    let out = dir.run_input(&["--capture"], &[], "\n\n");
+    assert!(String::from_utf8_lossy(&out.stderr)
+                   .contains("Nothing to capture"));
+}
+
+
+#[test]
+fn concurrent_captures_keep_all_items() {
+    let dir = TestDir::new("concurrent_captures_keep_all_items");
+    thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| {
+                let out = dir.run_input(&["--capture"], &[], "Same heading\n");
+                assert!(out.status.success());
+            });
+        }
+    });
+    let items = fs::read_dir(&dir.todo_dir).unwrap().filter(|entry| {
+        let name = entry.as_ref().unwrap().file_name();
+        name.to_string_lossy().starts_with("same-heading")
+    }).count();
+    assert_eq!(items, 8);
+}
@@ -0,0 +1,53 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use common::TestDir;
+
+const TODAY: &str = "2016-03-01";
+const PACK: &str = "Pack\ndate: 2016-03-01\n\nThings:\n- [ ] socks\n  \
+                            * [X] shoes\n- [ ] hat\nnot [ ] a step\n";
+
+
+#[test]
+fn listings_show_checklist_progress() {
+    let dir = TestDir::new("listings_show_checklist_progress");
+    dir.add_item("pack", PACK);
+    dir.add_item("other", "Other\ndate: 2016-03-01\n\n- item\n");
+    let out = dir.run_ok(&["--date", TODAY, "-t"]);
+    assert!(out.contains("]: Pack (1/3)\n"), "{}", out);
+    assert!(out.contains("]: Other\n"), "{}", out);
+}
+
+
+#[test]
+fn check_step_toggles_only_the_mark() {
+    let dir = TestDir::new("check_step_toggles_only_the_mark");
+    dir.add_item("pack", PACK);
+    let out = dir.run_ok(&["--check-step", "pack", "1"]);
+    assert!(out.ends_with("]: Pack: [x] socks\n"), "{}", out);
+    dir.run_ok(&["--check-step", "pack", "2"]);
+    assert_eq!(dir.read_item("pack"), PACK.replace("- [ ] socks", "- [x] socks")
+                                          .replace("* [X]", "* [ ]"));
+
+    let out = dir.run_ok(&["--date", TODAY, "-t"]);
+    assert!(out.contains("]: Pack (1/3)\n"), "{}", out);
+    dir.run_ok(&["--undo"]);
+    dir.run_ok(&["--undo"]);
+    assert_eq!(dir.read_item("pack"), PACK);
+}
+
+
+#[test]
+fn check_step_errors() {
+    let dir = TestDir::new("check_step_errors");
+    dir.add_item("pack", PACK);
+    let out = dir.run(&["--check-step", "pack", "4"], &[]);
+    assert!(String::from_utf8_lossy(&out.stderr)
+                   .contains("'Pack' has no step 4"));
+    let out = dir.run(&["--check-step", "pack", "0"], &[]);
+    assert!(String::from_utf8_lossy(&out.stderr)
+                   .contains("Invalid '--check-step' step '0'"));
+    assert_eq!(dir.read_item("pack"), PACK);
+}
@@ -0,0 +1,92 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use common::TestDir;
+
+
+const ITEMS: &[(&str, &str)] = &[
+    ("past",  "Past task\ndate: 2016-02-18\n"),
+    ("today", "Today's task\ndate: 2016-02-20\n"),
+    ("done",  "Done task\ndate: 2016-02-20\nstatus: done\n"),
+    ("soon",  "Upcoming task\ndate: 2016-02-23\n"),
+    ("later", "Far away task\ndate: 2016-03-20\n"),
+];
+
+
+#[test]
+fn today_uses_date_option() {
+    let dir = TestDir::with_items("today_uses_date_option", ITEMS);
+    let out = dir.run_ok(&["-t", "--date", "2016-02-20"]);
+
+    assert!(out.contains("Items for today, 2016-02-20"));
+    assert!(out.contains("Today's task"));
+    assert!(out.contains("Past unfinished tasks:\n2016-02-18:"));
+    assert!(out.contains("Past task"));
+    assert!(!out.contains("Done task"));
+    assert!(!out.contains("Upcoming task"));
+}
+
+
+#[test]
+fn today_only_uses_date_option() {
+    let dir = TestDir::with_items("today_only_uses_date_option", ITEMS);
+    let out = dir.run_ok(&["-T", "--date", "2016-02-18"]);
+
+    assert!(out.contains("Items for today, 2016-02-18"));
+    assert!(out.contains("Past task"));
+    assert!(!out.contains("Today's task"));
+}
+
+
+#[test]
+fn agenda_uses_date_option() {
+    let dir = TestDir::with_items("agenda_uses_date_option", ITEMS);
+    let out = dir.run_ok(&["-a", "--date", "2016-02-20"]);
+
+    assert!(out.contains("2016-02-23:"));
+    assert!(out.contains("Upcoming task"));
+    assert!(!out.contains("Far away task"));
+}
+
+
+#[test]
+fn todo_now_sets_today() {
+    let dir = TestDir::with_items("todo_now_sets_today", ITEMS);
+    let out = dir.run_env_ok(&["-t"], &[("TODO_NOW", "2016-02-23")]);
+
+    assert!(out.contains("Items for today, 2016-02-23"));
+    assert!(out.contains("Upcoming task"));
+}
+
+
+#[test]
+fn date_option_overrides_todo_now() {
+    let dir = TestDir::with_items("date_option_overrides_todo_now", ITEMS);
+    let out = dir.run_env_ok(&["-t", "--date", "2016-02-20"],
+                             &[("TODO_NOW", "2016-02-23")]);
+
+    assert!(out.contains("Items for today, 2016-02-20"));
+}
+
+
+#[test]
+fn invalid_date_is_an_error() {
+    let dir = TestDir::with_items("invalid_date_is_an_error", ITEMS);
+    let out = dir.run(&["-t", "--date", "2016-13-45"], &[]);
+
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Invalid date"));
+}
+
+
+#[test]
+fn rollover_uses_date_option() {
+    let dir = TestDir::with_items("rollover_uses_date_option", ITEMS);
+    dir.run_ok(&["-R", "--date", "2016-02-20"]);
+
+    assert_eq!(dir.read_item("past"),
+               "Past task\ndate: 2016-02-20\npostponed: 1\n");
+    assert_eq!(dir.read_item("soon"), "Upcoming task\ndate: 2016-02-23\n");
+}
@@ -0,0 +1,56 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use common::TestDir;
+
+
+const ITEMS: &[(&str, &str)] = &[
+    ("overdue", "Overdue\ndate: 2016-02-18\n"),
+    ("today",   "Today's task\ndate: 2016-02-20\npriority: low\n"),
+    ("wide",    "日本語の長い見出し\ndate: 2016-02-20\n"),
+];
+
+
+#[test]
+fn no_colors_when_not_a_terminal() {
+    let dir = TestDir::with_items("no_colors_when_not_a_terminal", ITEMS);
+    let out = dir.run_ok(&["-t", "--date", "2016-02-20"]);
+
+    assert!(!out.contains("\x1b["));
+}
+
+
+#[test]
+fn color_always_colors_overdue_and_today() {
+    let dir = TestDir::with_items("color_always_colors_overdue_and_today",
+                                  ITEMS);
+    let out = dir.run_ok(&["-t", "--date", "2016-02-20", "--color=always"]);
+
+    assert!(out.contains("\x1b[31mOverdue\x1b[0m"));
+    assert!(out.contains("\x1b[1;36mToday's task\x1b[0m"));
+}
+
+
+#[test]
+fn config_sets_theme_and_styles() {
+    let dir = TestDir::with_items("config_sets_theme_and_styles", ITEMS);
+    dir.add_item(".config", "theme: mono\ncolor-low: green\n");
+    let out = dir.run_ok(&["-t", "--date", "2016-02-20", "--color=always"]);
+
+    assert!(out.contains("\x1b[4mOverdue\x1b[0m"));
+    assert!(out.contains("\x1b[1;32mToday's task\x1b[0m"));
+}
+
+
+#[test]
+fn headings_are_truncated_by_display_width() {
+    let dir = TestDir::with_items("headings_are_truncated_by_display_width",
+                                  ITEMS);
+    let out = dir.run_env_ok(&["-T", "--date", "2016-02-20"],
+                             &[("COLUMNS", "26")]);
+
+    // "        [  3]: " leaves 11 columns: five wide characters and "…"
+    assert!(out.contains("]: 日本語の長…\n"));
+}
@@ -0,0 +1,120 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+#![allow(dead_code)]
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// A throwaway home directory with a `.todo` dir in it. Removed on drop.
+pub struct TestDir {
+    pub home:       PathBuf,
+    pub todo_dir:   PathBuf,
+}
+
+
+impl TestDir {
+    pub fn new(name: &str) -> TestDir {
+        let mut home = env::temp_dir();
+        home.push(format!("todo-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+
+        let mut todo_dir = home.clone();
+        todo_dir.push(".todo");
+        fs::create_dir_all(&todo_dir).unwrap();
+
+        TestDir { home, todo_dir }
+    }
+
+
+    /// Make a test dir with the items given as (file name, contents).
+    pub fn with_items(name: &str, items: &[(&str, &str)]) -> TestDir {
+        let dir = TestDir::new(name);
+        for &(filename, contents) in items {
+            dir.add_item(filename, contents);
+        }
+        dir
+    }
+
+
+    pub fn add_item(&self, filename: &str, contents: &str) {
+        let mut fd = File::create(self.todo_dir.join(filename)).unwrap();
+        fd.write_all(contents.as_bytes()).unwrap();
+    }
+
+
+    pub fn read_item(&self, filename: &str) -> String {
+        let mut contents = String::new();
+        let mut fd = File::open(self.todo_dir.join(filename)).unwrap();
+        fd.read_to_string(&mut contents).unwrap();
+        contents
+    }
+
+
+    /// Make an editor command that writes `contents` to the file it edits,
+    /// run through sh to have an editor with arguments.
+    pub fn editor(&self, contents: &str) -> String {
+        let script = self.home.join("editor.sh");
+        // $1 is the argument given in the command, $2 the edited file
+        let text = format!("printf '{}' > \"$2\"\n",
+                           contents.replace('\n', "\\n"));
+        self.add_item("../editor.sh", &text);
+        format!("sh '{}' ignored", script.display())
+    }
+
+
+    fn command(&self, args: &[&str], envs: &[(&str, &str)]) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_todo"));
+        cmd.args(args)
+           .env("HOME", &self.home)
+           .env_remove("TODO_NOW")
+           .env_remove("EDITOR")
+           .env_remove("VISUAL");
+        for &(key, val) in envs {
+            cmd.env(key, val);
+        }
+        cmd
+    }
+
+
+    pub fn run(&self, args: &[&str], envs: &[(&str, &str)]) -> Output {
+        self.command(args, envs).output().unwrap()
+    }
+
+
+    /// Run todo with `input` as its standard input.
+    pub fn run_input(&self, args: &[&str], envs: &[(&str, &str)],
+                     input: &str) -> Output {
+        let mut child = self.command(args, envs)
+                            .stdin(Stdio::piped())
+                            .stdout(Stdio::piped())
+                            .stderr(Stdio::piped())
+                            .spawn().unwrap();
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+
+    pub fn run_env_ok(&self, args: &[&str], envs: &[(&str, &str)])
+                      -> String {
+        let out = self.run(args, envs);
+        assert!(out.status.success(), "todo {:?} failed: {}", args,
+                String::from_utf8_lossy(&out.stderr));
+        String::from_utf8(out.stdout).unwrap()
+    }
+
+
+    pub fn run_ok(&self, args: &[&str]) -> String {
+        self.run_env_ok(args, &[])
+    }
+}
+
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.home);
+    }
+}
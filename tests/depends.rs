@@ -0,0 +1,67 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use common::TestDir;
+
+const TODAY: &str = "2016-03-01";
+
+
+const ITEMS: &[(&str, &str)] = &[
+    ("release", "Release 1.0\ndate: 2016-03-01\ndepends: docs, tests\n"),
+    ("docs",    "Write docs\ndate: 2016-03-01\n"),
+    ("tests",   "Fix tests\ndate: 2016-03-05\nstatus: done\n"),
+];
+
+
+#[test]
+fn blocked_items_are_left_out_of_today() {
+    let dir = TestDir::with_items("blocked_items_are_left_out_of_today",
+                                  ITEMS);
+    let out = dir.run_ok(&["--date", TODAY, "-t"]);
+    assert!(!out.contains("Release 1.0"), "{}", out);
+    assert!(out.contains("Write docs"));
+    assert!(out.ends_with("1 blocked items not shown, see --blocked\n"));
+
+    let out = dir.run_ok(&["--date", TODAY, "-t", "--with-blocked"]);
+    assert!(out.contains("Release 1.0"), "{}", out);
+
+    // done dependencies do not block
+    dir.run_ok(&["--done", "docs"]);
+    let out = dir.run_ok(&["--date", TODAY, "-t"]);
+    assert!(out.contains("Release 1.0"), "{}", out);
+}
+
+
+#[test]
+fn ready_and_blocked_views() {
+    let dir = TestDir::with_items("ready_and_blocked_views", ITEMS);
+    let out = dir.run_ok(&["--ready"]);
+    assert!(out.contains("Write docs"));
+    assert!(!out.contains("Release 1.0"));
+
+    let out = dir.run_ok(&["--blocked"]);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", out);
+    assert!(lines[1].ends_with("]: Release 1.0"));
+    assert!(lines[2].trim().starts_with("waits for ["));
+    assert!(lines[2].ends_with("] Write docs"));
+}
+
+
+#[test]
+fn graph_and_cycles() {
+    let dir = TestDir::with_items("graph_and_cycles", ITEMS);
+    let out = dir.run_ok(&["--graph"]);
+    assert!(out.starts_with("digraph todo {\n"));
+    assert!(out.contains("[label=\"Fix tests\", color=gray, fontcolor=gray];"));
+    assert_eq!(out.matches(" -> ").count(), 2);
+
+    dir.add_item("docs", "Write docs\ndepends: release\n");
+    let out = dir.run(&["--graph"], &[]);
+    assert_eq!(out.stdout.len(), 0);
+    assert!(String::from_utf8_lossy(&out.stderr)
+                   .contains("Dependency cycle: 'Release 1.0' -> \
+                              'Write docs' -> 'Release 1.0'"));
+}
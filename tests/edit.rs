@@ -0,0 +1,71 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use std::fs;
+
+use common::TestDir;
+
+
+#[test]
+fn editor_can_have_arguments() {
+    let dir = TestDir::new("editor_can_have_arguments");
+    dir.add_item("item", "Item\ndate: 2016-02-01\n");
+    let editor = dir.editor("Edited\ndate: 2016-02-02\n");
+
+    dir.run_env_ok(&["-e", "-i", "1"], &[("EDITOR", &editor)]);
+    assert_eq!(dir.read_item("item"), "Edited\ndate: 2016-02-02\n");
+}
+
+
+#[test]
+fn broken_edit_can_be_reverted() {
+    let dir = TestDir::new("broken_edit_can_be_reverted");
+    dir.add_item("item", "Item\ndate: 2016-02-01\n");
+    let editor = dir.editor("Item\ndate: 2016-13-01\n");
+
+    // an unknown answer is asked again
+    let out = dir.run_input(&["-e", "-i", "1"], &[("EDITOR", &editor)],
+                            "x\nr\n");
+    assert!(String::from_utf8_lossy(&out.stderr)
+                   .contains("invalid date '2016-13-01'"));
+    assert!(String::from_utf8_lossy(&out.stdout)
+                   .ends_with("Reverted to the original.\n"));
+    assert_eq!(dir.read_item("item"), "Item\ndate: 2016-02-01\n");
+}
+
+
+#[test]
+fn broken_edit_is_kept_without_answer() {
+    let dir = TestDir::new("broken_edit_is_kept_without_answer");
+    dir.add_item("item", "Item\ndate: 2016-02-01\n");
+    let editor = dir.editor("\n");
+
+    let out = dir.run(&["-e", "-i", "1"], &[("EDITOR", &editor)]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Heading empty"));
+    assert_eq!(dir.read_item("item"), "\n");
+}
+
+
+#[test]
+fn changes_made_while_editing_are_not_overwritten() {
+    let dir = TestDir::new("changes_made_while_editing_are_not_overwritten");
+    dir.add_item("item", "Item\ndate: 2016-02-01\n");
+    // the item is changed elsewhere while the editor is open
+    let script = format!("printf 'Changed\\ndate: 2016-02-03\\n' > '{}'\n\
+                          printf 'Edited\\ndate: 2016-02-02\\n' > \"$1\"\n",
+                         dir.todo_dir.join("item").display());
+    dir.add_item("../editor.sh", &script);
+    let editor = format!("sh '{}'", dir.home.join("editor.sh").display());
+
+    let out = dir.run(&["-e", "-i", "1"], &[("EDITOR", &editor)]);
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.contains("'item' has changed while it was edited"));
+    assert_eq!(dir.read_item("item"), "Changed\ndate: 2016-02-03\n");
+
+    let kept = err.split('\'').nth(3).unwrap();
+    assert_eq!(fs::read_to_string(kept).unwrap(),
+               "Edited\ndate: 2016-02-02\n");
+    fs::remove_file(kept).unwrap();
+}
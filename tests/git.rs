@@ -0,0 +1,97 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use std::process::Command;
+
+use common::TestDir;
+
+
+fn git(dir: &TestDir, args: &[&str]) -> String {
+    let out = Command::new("git").arg("-C").arg(&dir.todo_dir).args(args)
+                                 .output().unwrap();
+    assert!(out.status.success(), "git {:?} failed: {}", args,
+            String::from_utf8_lossy(&out.stderr));
+    String::from_utf8(out.stdout).unwrap()
+}
+
+
+const ITEMS: &[(&str, &str)] = &[
+    (".config",    "git: yes\n"),
+    (".gitignore", ".cache\n.journal\n.lock\n"),
+    ("item",       "Item\ndate: 2016-02-01\n"),
+];
+
+
+fn repo_dir(name: &str) -> TestDir {
+    let dir = TestDir::with_items(name, ITEMS);
+    git(&dir, &["init", "-q"]);
+    git(&dir, &["config", "user.name", "Tester"]);
+    git(&dir, &["config", "user.email", "tester@example.com"]);
+    git(&dir, &["add", "-A"]);
+    git(&dir, &["commit", "-q", "-m", "Add item"]);
+    dir
+}
+
+
+#[test]
+fn changes_are_committed() {
+    let dir = repo_dir("changes_are_committed");
+    dir.run_ok(&["-R", "--date", "2016-02-10"]);
+    dir.run_ok(&["-p", "1", "--to", "2016-02-12", "--date", "2016-02-10"]);
+
+    assert_eq!(git(&dir, &["log", "--format=%s"]),
+               "Postpone 'Item' to 2016-02-12\n\
+                Roll over 1 items to 2016-02-10\n\
+                Add item\n");
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "");
+
+    let out = dir.run_ok(&["--history", "1"]);
+    assert!(out.starts_with("History of [1] Item:\n"));
+    assert_eq!(out.lines().count(), 4);
+}
+
+
+#[test]
+fn undo_and_redo_are_committed() {
+    let dir = repo_dir("undo_and_redo_are_committed");
+    dir.run_ok(&["-R", "--date", "2016-02-10"]);
+    dir.run_ok(&["-p", "1", "--to", "2016-02-12", "--date", "2016-02-10"]);
+
+    dir.run_ok(&["--undo", "2"]);
+    assert_eq!(dir.read_item("item"), "Item\ndate: 2016-02-01\n");
+    dir.run_ok(&["--redo"]);
+    assert_eq!(git(&dir, &["log", "-3", "--format=%s"]),
+               "Redo: Roll over 1 items to 2016-02-10\n\
+                Undo: Roll over 1 items to 2016-02-10\n\
+                Undo: Postpone 'Item' to 2016-02-12\n");
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "");
+}
+
+
+#[test]
+fn deleting_is_committed() {
+    let dir = repo_dir("deleting_is_committed");
+    dir.run_ok(&["-X", "1"]);
+    assert_eq!(git(&dir, &["log", "-1", "--format=%s"]), "Delete 'Item'\n");
+    assert_eq!(git(&dir, &["ls-files", "item"]), "");
+    assert_eq!(git(&dir, &["status", "--porcelain"]), "");
+}
+
+
+#[test]
+fn commits_without_journal_are_reverted() {
+    let dir = repo_dir("commits_without_journal_are_reverted");
+    // committed by todo before it kept a journal
+    dir.add_item("item", "Item\ndate: 2016-02-05\n");
+    git(&dir, &["commit", "-q", "-a", "-m",
+                "Postpone 'Item'\n\nTodo-Action: postpone"]);
+
+    let out = dir.run_ok(&["--undo"]);
+    assert_eq!(out, "Undid: Postpone 'Item'\n");
+    assert_eq!(dir.read_item("item"), "Item\ndate: 2016-02-01\n");
+    assert_eq!(git(&dir, &["log", "-1", "--format=%s"]),
+               "Undo: Postpone 'Item'\n");
+    assert!(!dir.run(&["--undo"], &[]).stderr.is_empty());
+}
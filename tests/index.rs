@@ -0,0 +1,74 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use common::TestDir;
+
+
+const ITEMS: &[(&str, &str)] = &[
+    (".config",  "index: sqlite\n"),
+    ("old",      "Old\ndate: 2016-01-10\nstatus: done\n"),
+    ("overdue",  "Overdue\ndate: 2016-02-01\n"),
+    ("today",    "Today\ndate: 2016-02-10\n"),
+    ("dateless", "Dateless\n"),
+];
+
+
+#[test]
+fn index_gives_same_views() {
+    let dir = TestDir::with_items("index_gives_same_views", ITEMS);
+    for args in &[&["-t"][..], &["-T"][..], &["-a", "--past", "40"][..],
+                  &["-w"][..], &["-R", "-n"][..]] {
+        let mut args = args.to_vec();
+        args.extend_from_slice(&["--date", "2016-02-10"]);
+        let indexed = dir.run_ok(&args);
+
+        dir.add_item(".config", "index: none\n");
+        assert_eq!(indexed, dir.run_ok(&args));
+        dir.add_item(".config", "index: sqlite\n");
+    }
+    assert!(dir.todo_dir.join(".index.sqlite").exists());
+}
+
+
+#[test]
+fn index_follows_file_changes() {
+    let dir = TestDir::with_items("index_follows_file_changes", ITEMS);
+    let out = dir.run_ok(&["-T", "--date", "2016-02-10"]);
+    assert!(out.contains("Today"));
+
+    dir.add_item("today", "Changed heading\ndate: 2016-02-10\n");
+    dir.add_item("new", "New one\ndate: 2016-02-10\n");
+    let out = dir.run_ok(&["-T", "--date", "2016-02-10"]);
+    assert!(out.contains("Changed heading"));
+    assert!(out.contains("New one"));
+
+    std::fs::remove_file(dir.todo_dir.join("new")).unwrap();
+    let out = dir.run_ok(&["-T", "--date", "2016-02-10"]);
+    assert!(!out.contains("New one"));
+}
+
+
+#[test]
+fn index_selects_items_by_filters() {
+    let dir = TestDir::with_items("index_selects_items_by_filters", ITEMS);
+    dir.add_item("work", "Work\ndate: 2016-02-10\ntags: home, work\n");
+    dir.add_item("homework", "Homework\ndate: 2016-02-10\ntags: homework\n");
+    dir.add_item("done-work", "Done work\ndate: 2016-02-10\nstatus: DONE\n\
+                               tags: work\n");
+    for selector in &["tags=work", "status=todo", "status=done,tags=homework"] {
+        let args = ["-n", "--yes", "-p", selector, "--date", "2016-02-10"];
+        let indexed = dir.run_ok(&args);
+
+        dir.add_item(".config", "index: none\n");
+        assert_eq!(indexed, dir.run_ok(&args));
+        dir.add_item(".config", "index: sqlite\n");
+    }
+
+    let out = dir.run_ok(&["-n", "-p", "tags=work", "--date", "2016-02-10"]);
+    assert!(out.contains("Work") && out.contains("Done work"));
+    assert!(!out.contains("Homework"));
+}
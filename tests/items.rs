@@ -0,0 +1,79 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use std::fs;
+
+use common::TestDir;
+
+
+#[test]
+fn listing_stops_at_the_body() {
+    let dir = TestDir::new("listing_stops_at_the_body");
+    // a body that is not UTF-8 can not be read as a whole
+    let mut contents = b"Item\ndate: 2016-02-10\n\ndate: 2016-02-20\n".to_vec();
+    contents.extend_from_slice(b"\xff\xfe not text\n");
+    fs::write(dir.todo_dir.join("item"), &contents).unwrap();
+
+    let out = dir.run(&["-T", "--date", "2016-02-10"], &[]);
+    assert_eq!(out.stderr.len(), 0, "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("]: Item"));
+    let out = dir.run_ok(&["-a", "--date", "2016-02-10"]);
+    assert!(out.starts_with("Agenda for the next 8 days, 2016-02-10"));
+    assert!(!out.contains("2016-02-20:"));
+}
+
+
+#[test]
+fn show_reads_the_body() {
+    let dir = TestDir::new("show_reads_the_body");
+    dir.add_item("item", "Item\ndate: 2016-02-10\n\nFirst line\nSecond\n");
+
+    let out = dir.run_ok(&["-s", "-i", "1"]);
+    assert!(out.ends_with("heading: Item\n\nFirst line\nSecond\n\n"));
+}
+
+
+#[test]
+fn cache_follows_file_changes() {
+    let dir = TestDir::new("cache_follows_file_changes");
+    dir.add_item("item", "Item\ndate: 2016-02-10\n");
+
+    assert!(dir.run_ok(&["-T", "--date", "2016-02-10"]).contains("Item"));
+    assert!(dir.todo_dir.join(".cache").exists());
+
+    dir.add_item("item", "Renamed item\ndate: 2016-02-10\n");
+    let out = dir.run_ok(&["-T", "--date", "2016-02-10"]);
+    assert!(out.contains("Renamed item"));
+}
+
+
+#[test]
+fn corrupt_cache_is_rebuilt() {
+    let dir = TestDir::new("corrupt_cache_is_rebuilt");
+    dir.add_item("item", "Item\ndate: 2016-02-10\n");
+    let out = dir.run_ok(&["-T", "--date", "2016-02-10"]);
+    let path = dir.todo_dir.join(".cache");
+    let cache = fs::read(&path).unwrap();
+
+    fs::write(&path, &cache[..cache.len() - 3]).unwrap();
+    assert_eq!(out, dir.run_ok(&["-T", "--date", "2016-02-10"]));
+    assert_eq!(cache, fs::read(&path).unwrap());
+}
+
+
+#[test]
+fn order_does_not_depend_on_the_cache() {
+    let dir = TestDir::new("order_does_not_depend_on_the_cache");
+    for name in &["a", "c", "d", "x"] {
+        dir.add_item(name, &format!("Item {}\ndate: 2016-02-10\n", name));
+    }
+    dir.run_ok(&["-T", "--date", "2016-02-10"]);
+
+    // a changed file is a cache miss, loaded after the hits
+    dir.add_item("a", "Item a, changed\ndate: 2016-02-10\n");
+    let cached = dir.run_ok(&["-T", "--date", "2016-02-10"]);
+    fs::remove_file(dir.todo_dir.join(".cache")).unwrap();
+    assert_eq!(cached, dir.run_ok(&["-T", "--date", "2016-02-10"]));
+}
@@ -0,0 +1,59 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use std::path::Path;
+
+use common::TestDir;
+
+
+#[test]
+fn undo_and_redo_replay_the_journal() {
+    let dir = TestDir::new("undo_and_redo_replay_the_journal");
+    dir.add_item("item", "Item\ndate: 2016-02-01\n\nBody\n");
+    dir.run_ok(&["-R", "--date", "2016-02-10"]);
+    dir.run_ok(&["-p", "1", "--to", "2016-02-12", "--date", "2016-02-10"]);
+
+    assert_eq!(dir.run_ok(&["--undo", "2"]),
+               "Undid: Postpone 'Item' to 2016-02-12\n\
+                Undid: Roll over 1 items to 2016-02-10\n");
+    assert_eq!(dir.read_item("item"), "Item\ndate: 2016-02-01\n\nBody\n");
+
+    assert_eq!(dir.run_ok(&["--redo"]),
+               "Redid: Roll over 1 items to 2016-02-10\n");
+    assert!(dir.read_item("item").contains("date: 2016-02-10\n"));
+
+    let log = dir.run_ok(&["--log"]);
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with("postpone: Postpone 'Item' to 2016-02-12 (undone)"));
+    assert!(lines[1].ends_with("rollover: Roll over 1 items to 2016-02-10"));
+}
+
+
+#[test]
+fn undo_brings_back_deleted_item() {
+    let dir = TestDir::new("undo_brings_back_deleted_item");
+    dir.add_item("item", "Item\ndate: 2016-02-01\n");
+    dir.run_ok(&["-X", "-i", "1"]);
+    assert!(!Path::new(&dir.todo_dir).join("item").exists());
+
+    dir.run_ok(&["--undo"]);
+    assert_eq!(dir.read_item("item"), "Item\ndate: 2016-02-01\n");
+    let out = dir.run(&["--undo"], &[]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Nothing to undo"));
+}
+
+
+#[test]
+fn undo_refuses_to_overwrite_outside_changes() {
+    let dir = TestDir::new("undo_refuses_to_overwrite_outside_changes");
+    dir.add_item("item", "Item\ndate: 2016-02-01\n");
+    dir.run_ok(&["-R", "--date", "2016-02-10"]);
+    dir.add_item("item", "Item\ndate: 2016-03-01\n");
+
+    let out = dir.run(&["--undo"], &[]);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("has changed"));
+    assert_eq!(dir.read_item("item"), "Item\ndate: 2016-03-01\n");
+}
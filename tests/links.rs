@@ -0,0 +1,51 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use common::TestDir;
+
+
+const ITEMS: &[(&str, &str)] = &[
+    ("trip",    "Plan trip\nrelated: hotel\n\nSee [[book flights]] and \
+                 [[Nothing here]].\n"),
+    ("flights", "Book flights\n"),
+    ("hotel",   "Book hotel\n"),
+];
+
+
+#[test]
+fn show_prints_links() {
+    let dir = TestDir::with_items("show_prints_links", ITEMS);
+    let out = dir.run_ok(&["-s", "trip"]);
+    let links: Vec<&str> = out.lines().filter(|l| l.starts_with("links to: "))
+                              .collect();
+    assert_eq!(links.len(), 3, "{}", out);
+    assert!(links[0].ends_with("] Book flights"));
+    assert_eq!(links[1], "links to: 'Nothing here' (not found)");
+    assert!(links[2].ends_with("] Book hotel"));
+}
+
+
+#[test]
+fn show_prints_backlinks() {
+    let dir = TestDir::with_items("show_prints_backlinks", ITEMS);
+    let out = dir.run_ok(&["-s", "hotel"]);
+    assert!(out.contains("\nreferenced by: ["), "{}", out);
+    assert!(out.contains("] Plan trip\n"), "{}", out);
+    assert!(!out.contains("links to:"));
+}
+
+
+#[test]
+fn check_warns_about_broken_links() {
+    let dir = TestDir::with_items("check_warns_about_broken_links", ITEMS);
+    let out = dir.run(&["--check"], &[]);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("'Plan trip'"));
+    assert!(stderr.contains("links to 'Nothing here', which does not exist"));
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "1 broken links.\n");
+
+    dir.add_item("nothing", "Nothing here\n");
+    assert_eq!(dir.run_ok(&["--check"]), "No problems found.\n");
+}
@@ -0,0 +1,69 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use common::TestDir;
+
+
+const ITEMS: &[(&str, &str)] = &[
+    ("task1",  "Task 1\ndate: 2016-02-01\n"),
+    ("task2",  "Task 2\ndate: 2016-02-02\n"),
+    ("task3",  "Task 3\ndate: 2016-02-03\n"),
+    ("task4",  "Task 4\ndate: 2016-02-04\n"),
+    ("task5",  "Task 5\ndate: 2016-02-05\n"),
+    ("urgent", "Urgent\npriority: high\n"),
+];
+
+
+fn id_of(dir: &TestDir, file: &str) -> String {
+    let out = dir.run_ok(&["-s", file]);
+    out.lines().next().unwrap()["id: ".len()..].to_string()
+}
+
+
+#[test]
+fn lists_and_ranges_select_many_items() {
+    let dir = TestDir::with_items("lists_and_ranges_select_many_items", ITEMS);
+    let ids = format!("{},{}", id_of(&dir, "task1"), id_of(&dir, "urgent"));
+    dir.run_ok(&["--done", &ids]);
+    assert!(dir.read_item("task1").ends_with("status: done\n"));
+    assert!(dir.read_item("urgent").ends_with("status: done\n"));
+    assert!(!dir.read_item("task2").contains("status"));
+
+    // one change in the journal
+    dir.run_ok(&["--undo"]);
+    assert!(!dir.read_item("task1").contains("status"));
+    assert!(!dir.read_item("urgent").contains("status"));
+
+    let out = dir.run(&["-s", "1-2"], &[]);
+    assert!(String::from_utf8_lossy(&out.stderr)
+                   .contains("'1-2' selects 2 items, select one"));
+}
+
+
+#[test]
+fn attr_filter_selects_items_to_tag() {
+    let dir = TestDir::with_items("attr_filter_selects_items_to_tag", ITEMS);
+    dir.run_ok(&["--tag", "work", "-i", "priority=high"]);
+    assert_eq!(dir.read_item("urgent"),
+               "Urgent\npriority: high\ntags: work\n");
+    dir.run_ok(&["--tag", "today", "-i", "tags=work"]);
+    assert_eq!(dir.read_item("urgent"),
+               "Urgent\npriority: high\ntags: work, today\n");
+}
+
+
+#[test]
+fn many_items_need_confirmation() {
+    let dir = TestDir::with_items("many_items_need_confirmation", ITEMS);
+    let out = dir.run_input(&["-X", "1-6"], &[], "n\n");
+    let out = String::from_utf8_lossy(&out.stdout);
+    assert!(out.starts_with("Delete these 6 items?\n"));
+    assert!(out.ends_with("Nothing changed.\n"));
+    assert!(dir.todo_dir.join("task1").exists());
+
+    dir.run_ok(&["-X", "1-6", "--yes"]);
+    assert!(!dir.todo_dir.join("task1").exists());
+    assert!(!dir.todo_dir.join("urgent").exists());
+}
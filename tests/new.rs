@@ -0,0 +1,71 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use std::fs;
+
+use common::TestDir;
+
+
+fn item_names(dir: &TestDir) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(&dir.todo_dir).unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    names.sort();
+    names
+}
+
+
+#[test]
+fn new_item_is_named_after_heading() {
+    let dir = TestDir::new("new_item_is_named_after_heading");
+    dir.add_item("buy-milk", "Buy milk\n");
+    let editor = dir.editor("Buy milk!\ndate: 2016-02-05\n");
+
+    let out = dir.run_env_ok(&["--new"], &[("EDITOR", &editor)]);
+    assert!(out.starts_with("Added "));
+    assert_eq!(item_names(&dir), vec!["buy-milk", "buy-milk-2"]);
+    assert_eq!(dir.read_item("buy-milk-2"), "Buy milk!\ndate: 2016-02-05\n");
+}
+
+
+#[test]
+fn template_is_filled_in_and_unchanged_discarded() {
+    let dir = TestDir::new("template_is_filled_in_and_unchanged_discarded");
+    fs::create_dir(dir.todo_dir.join(".templates")).unwrap();
+    dir.add_item(".templates/call", "Call {{user}} {{today}}\n\
+                                     date: {{tomorrow}}\n");
+
+    // an editor that saves the file as it is
+    let envs = [("EDITOR", "true"), ("USER", "tester"),
+                ("TODO_NOW", "2016-02-29")];
+    let out = dir.run_env_ok(&["--new", "call"], &envs);
+    assert_eq!(out, "No changes, discarded the new item.\n");
+    assert_eq!(item_names(&dir), Vec::<String>::new());
+
+    // an editor that adds a body
+    let envs = [("EDITOR", "sh -c 'echo \"\nBody\" >> \"$1\"' sh"),
+                ("USER", "tester"), ("TODO_NOW", "2016-02-29")];
+    let out = dir.run_env_ok(&["--new=call"], &envs);
+    assert!(out.starts_with("Added "));
+    assert_eq!(dir.read_item("call-tester-2016-02-29"),
+               "Call tester 2016-02-29\ndate: 2016-03-01\n\nBody\n");
+}
+
+
+#[test]
+fn template_outside_template_dir_is_unknown() {
+    let dir = TestDir::new("template_outside_template_dir_is_unknown");
+    fs::create_dir(dir.todo_dir.join(".templates")).unwrap();
+    dir.add_item("../secret", "Secret\n");
+
+    let out = dir.run(&["--new", "../../secret"], &[("EDITOR", "true")]);
+    assert!(String::from_utf8_lossy(&out.stderr)
+                   .contains("No template '../../secret' in .templates"));
+    let out = dir.run(&["--new", "/etc/passwd"], &[("EDITOR", "true")]);
+    assert!(String::from_utf8_lossy(&out.stderr)
+                   .contains("No template '/etc/passwd' in .templates"));
+    assert_eq!(item_names(&dir), Vec::<String>::new());
+}
@@ -0,0 +1,63 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use common::TestDir;
+
+
+const ITEMS: &[(&str, &str)] = &[
+    ("milk", "Buy milk\ndate: 2016-03-01\n\nOat milk, not cow.\n"),
+    ("bob",  "Call Bob\npriority: high\n\nAsk about MILK prices.\n"),
+    ("bike", "Fix bike\n"),
+];
+
+
+#[test]
+fn search_finds_text_in_headings_and_bodies() {
+    let dir = TestDir::with_items("search_finds_text_in_headings_and_bodies",
+                                  ITEMS);
+    let out = dir.run_ok(&["--search", "milk"]);
+    assert!(out.contains("Buy milk\n"));
+    assert!(out.contains("Oat milk, not cow."));
+    assert!(!out.contains("Call Bob"));
+
+    let out = dir.run_ok(&["--search", "milk", "--ignore-case"]);
+    assert!(out.contains("Call Bob"));
+    assert!(out.contains("Ask about MILK prices."));
+    assert!(!out.contains("Fix bike"));
+
+    let out = dir.run_ok(&["--search", "nothing"]);
+    assert_eq!(out, "No items match 'nothing'.\n");
+}
+
+
+#[test]
+fn search_with_regex_looks_in_attrs() {
+    let dir = TestDir::with_items("search_with_regex_looks_in_attrs", ITEMS);
+    let out = dir.run_ok(&["--search", "^priority: h", "--regex"]);
+    assert!(out.contains("Call Bob"));
+    assert!(!out.contains("Buy milk"));
+
+    // without --regex the pattern is plain text
+    let out = dir.run_ok(&["--search", "^priority: h"]);
+    assert!(!out.contains("Call Bob"));
+
+    let out = dir.run(&["--search", "(", "--regex"], &[]);
+    assert!(String::from_utf8_lossy(&out.stderr)
+                   .contains("Invalid search pattern '('"));
+}
+
+
+#[test]
+fn search_selects_items_for_actions() {
+    let dir = TestDir::with_items("search_selects_items_for_actions", ITEMS);
+    dir.run_ok(&["--done", "--search", "milk", "-I"]);
+    assert!(dir.read_item("milk").contains("status: done"));
+    assert!(dir.read_item("bob").contains("status: done"));
+    assert!(!dir.read_item("bike").contains("status"));
+
+    let out = dir.run(&["-s", "--search", "milk", "-I"], &[]);
+    assert!(String::from_utf8_lossy(&out.stderr)
+                   .contains("'milk' selects 2 items, select one"));
+}
@@ -0,0 +1,99 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use common::TestDir;
+
+
+const ITEMS: &[(&str, &str)] = &[
+    ("rel-1.0", "Release 1.0\ndate: 2016-02-01\n"),
+    ("notes",   "Release notes\ndate: 2016-02-02\n"),
+    ("weekly",  "Weekly report\ndate: 2016-02-03\n"),
+];
+
+
+fn shown_heading(out: &str) -> &str {
+    out.lines().find(|l| l.starts_with("heading: ")).unwrap()
+}
+
+
+#[test]
+fn heading_selects_one_item_or_lists_candidates() {
+    let dir = TestDir::with_items(
+        "heading_selects_one_item_or_lists_candidates", ITEMS);
+    let out = dir.run_ok(&["-s", "weekly REPORT"]);
+    assert_eq!(shown_heading(&out), "heading: Weekly report");
+    let out = dir.run_ok(&["-s", "wkrep"]);
+    assert_eq!(shown_heading(&out), "heading: Weekly report");
+
+    let out = dir.run(&["-X", "release"], &[]);
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.contains("'release' matches 2 items"));
+    assert!(err.contains("Release 1.0") && err.contains("Release notes"));
+    assert_eq!(dir.read_item("rel-1.0"), "Release 1.0\ndate: 2016-02-01\n");
+}
+
+
+#[test]
+fn file_name_and_stable_id_select_items() {
+    let dir = TestDir::with_items("file_name_and_stable_id_select_items",
+                                  ITEMS);
+    let out = dir.run_ok(&["-s", "notes"]);
+    assert_eq!(shown_heading(&out), "heading: Release notes");
+    let path = dir.todo_dir.join("rel-1.0");
+    let out = dir.run_ok(&["-i", path.to_str().unwrap(), "-s"]);
+    assert_eq!(shown_heading(&out), "heading: Release 1.0");
+
+    let stable_id = out.lines().find(|l| l.starts_with("stable id: "))
+                       .unwrap()["stable id: ".len()..].to_string();
+    let out = dir.run_ok(&["-s", &stable_id[..5]]);
+    assert_eq!(shown_heading(&out), "heading: Release 1.0");
+}
+
+
+#[test]
+fn headings_with_commas_and_dashes_are_not_split() {
+    let dir = TestDir::with_items(
+        "headings_with_commas_and_dashes_are_not_split", ITEMS);
+    dir.add_item("login", "Fix login, again\ndate: 2016-02-04\n");
+    dir.add_item("budget", "Budget 2016-02\n");
+    let out = dir.run_ok(&["-s", "-i", "fix login, again"]);
+    assert_eq!(shown_heading(&out), "heading: Fix login, again");
+    let out = dir.run_ok(&["-s", "-i", "2016-02"]);
+    assert_eq!(shown_heading(&out), "heading: Budget 2016-02");
+    let out = dir.run_ok(&["-s", "-i", "rel-1.0"]);
+    assert_eq!(shown_heading(&out), "heading: Release 1.0");
+}
+
+
+#[test]
+fn whole_heading_wins_over_partial_matches() {
+    let dir = TestDir::with_items(
+        "whole_heading_wins_over_partial_matches", ITEMS);
+    dir.add_item("draft", "Weekly report draft\ndate: 2016-02-04\n");
+    let out = dir.run_ok(&["-s", "weekly report"]);
+    assert_eq!(shown_heading(&out), "heading: Weekly report");
+    let out = dir.run_ok(&["-s", "Weekly Report Draft"]);
+    assert_eq!(shown_heading(&out), "heading: Weekly report draft");
+}
+
+
+#[test]
+fn digit_only_stable_id_prefix_is_not_taken_as_an_id() {
+    let dir = TestDir::with_items(
+        "digit_only_stable_id_prefix_is_not_taken_as_an_id", ITEMS);
+    // the stable IDs of these are 00012173, 0001f830 and 91073399
+    dir.add_item("task1377740", "Digits\ndate: 2016-02-04\n");
+    dir.add_item("step594520", "Steps\ndate: 2016-02-05\n");
+    dir.add_item("note1515", "More digits\ndate: 2016-02-06\n");
+
+    // item 1 exists too, but the stable IDs are not left out
+    let out = dir.run(&["-s", "0001"], &[]);
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.contains("'0001' matches"));
+    assert!(err.contains("Digits") && err.contains("Steps"));
+
+    let out = dir.run_ok(&["-s", "9107"]);
+    assert_eq!(shown_heading(&out), "heading: More digits");
+}
@@ -0,0 +1,63 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+#![cfg(unix)]
+
+extern crate libc;
+
+mod common;
+
+use std::fs::{self, File};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use common::TestDir;
+
+
+#[test]
+fn writes_keep_file_mode() {
+    let dir = TestDir::new("writes_keep_file_mode");
+    dir.add_item("item", "Item\ndate: 2016-02-01\n");
+    let path = dir.todo_dir.join("item");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+    dir.run_ok(&["-R", "--date", "2016-02-10"]);
+    assert_eq!(dir.read_item("item"),
+               "Item\ndate: 2016-02-10\npostponed: 1\n");
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    // no temporary files are left behind
+    assert_eq!(fs::read_dir(&dir.todo_dir).unwrap()
+                  .filter(|e| e.as_ref().unwrap().file_name()
+                               .to_string_lossy().contains(".tmp"))
+                  .count(), 0);
+}
+
+
+#[test]
+fn rollover_waits_for_lock() {
+    let dir = TestDir::new("rollover_waits_for_lock");
+    dir.add_item("item", "Item\ndate: 2016-02-01\n");
+
+    let lock = File::create(dir.todo_dir.join(".lock")).unwrap();
+    assert_eq!(unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) }, 0);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_todo"))
+                            .args(["-R", "--date", "2016-02-10"])
+                            .env("HOME", &dir.home)
+                            .stdout(Stdio::null())
+                            .stderr(Stdio::piped())
+                            .spawn().unwrap();
+    thread::sleep(Duration::from_millis(300));
+    assert!(child.try_wait().unwrap().is_none());
+    assert!(dir.read_item("item").contains("2016-02-01"));
+
+    drop(lock);
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Waiting for the lock"));
+    assert!(dir.read_item("item").contains("2016-02-10"));
+}
@@ -0,0 +1,64 @@
+// Copyright 2016 Tuomo Hartikainen <tth@harski.org>.
+// Licensed under the 2-clause BSD license, see LICENSE for details.
+
+mod common;
+
+use common::TestDir;
+
+const TODAY: &str = "2016-03-01";
+
+
+const ITEMS: &[(&str, &str)] = &[
+    ("trip",    "Plan trip\ndate: 2016-03-01\n"),
+    ("flights", "Book flights\ndate: 2016-03-01\nparent: trip\n\
+                 status: done\n"),
+    ("hotel",   "Book hotel\ndate: 2016-03-01\nparent: trip\n"),
+    ("other",   "Other\ndate: 2016-03-01\n"),
+];
+
+
+#[test]
+fn subtasks_are_listed_under_their_parent() {
+    let dir = TestDir::with_items("subtasks_are_listed_under_their_parent",
+                                  ITEMS);
+    let out = dir.run_ok(&["--date", TODAY, "-t"]);
+    let lines: Vec<&str> = out.lines().collect();
+    assert!(lines[1].ends_with("]: Plan trip (1/2 done)"), "{}", out);
+    assert!(lines[2].ends_with("]:   Book hotel"), "{}", out);
+    assert!(lines[3].ends_with("]: Other"), "{}", out);
+}
+
+
+#[test]
+fn parent_with_open_subtasks_is_not_marked_done() {
+    let dir = TestDir::with_items(
+        "parent_with_open_subtasks_is_not_marked_done", ITEMS);
+    let out = dir.run(&["--done", "trip"], &[]);
+    assert!(String::from_utf8_lossy(&out.stderr)
+                   .contains("'Plan trip' has 1 open subtasks"));
+    assert!(!dir.read_item("trip").contains("status"));
+
+    // done together with its subtasks, or when forced
+    dir.run_ok(&["--done", "trip,hotel"]);
+    assert!(dir.read_item("trip").contains("status: done"));
+    dir.run_ok(&["--undo"]);
+    dir.run_ok(&["--done", "trip", "--force"]);
+    assert!(dir.read_item("trip").contains("status: done"));
+    assert!(!dir.read_item("hotel").contains("status"));
+}
+
+
+#[test]
+fn add_with_parent_makes_a_subtask() {
+    let dir = TestDir::with_items("add_with_parent_makes_a_subtask", ITEMS);
+    let out = dir.run_ok(&["--date", TODAY, "-s", "trip"]);
+    let stable_id = out.lines().find(|l| l.starts_with("stable id: "))
+                       .unwrap()["stable id: ".len()..].to_string();
+
+    dir.run_ok(&["--date", TODAY, "--add", "Pack", "--parent", "Plan trip"]);
+    assert_eq!(dir.read_item("pack"),
+               format!("Pack\ndate: {}\nparent: {}\n", TODAY, stable_id));
+    let out = dir.run_ok(&["--date", TODAY, "-t"]);
+    assert!(out.contains("Plan trip (1/3 done)"), "{}", out);
+    assert!(out.contains("]:   Pack\n"), "{}", out);
+}